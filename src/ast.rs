@@ -21,12 +21,23 @@ pub enum Statement {
     Expression(Box<Expression>), // a statement that does nothing but evaluate a tail-expression
     UnboundLetAssign(Span, String, Box<Expression>), // unbound: needs to be bound by resolver
     UnboundGlobalAssign(Span, String, Box<Expression>),
-    Assign(Span, usize, Box<Expression>, Option<String>), // once bound, there's no useful distinction between `let` and `global`, so we only need one Assign
-                                                          // the option<string> allows us to build an export table in the interpreter
+    UnboundExportAssign(Span, String, Box<Expression>),
+    UnboundParamAssign(Span, String, Box<Expression>), // the expression is the default, for when the includer doesn't pass this parameter
+    Assign(Span, usize, Box<Expression>, Binding), // once bound, `let`, `global` and `export` differ only in where they put the value, so we only need one Assign
     Debugger(Span)
 }
 
 
+#[derive(Debug, Clone)]
+pub enum Binding {
+    Local,
+    Global,
+    Export(String), // a local that is also published as a property of the enclosing sitix expression.
+                    // the string allows us to build an export table in the interpreter
+    Param(String) // a local that takes its value from the parameters passed to include(), if there is one by this name
+}
+
+
 pub use crate::utility::Literal;
 
 #[derive(Debug, Clone)]
@@ -46,6 +57,7 @@ pub enum Expression {
     UnboundVariableAccess(Span, String), // a variable access that has not been bound by the resolver
     VariableAccess(Span, usize), // a fully bound variable access
     Assignment(Box<Expression>, Box<Expression>),
+    CompoundAssignment(Box<Expression>, CompoundOp, Box<Expression>), // x += y and co. x ++ is x += 1. like assignment, evaluates to the new value of x
     IfBranch(Span, Box<Expression>, Box<Expression>, Option<Box<Expression>>), // condition, true-branch, false-branch
     Table(Span, Vec<TableEntry>),
     While(Span, Box<Expression>, Box<Expression>),
@@ -54,7 +66,8 @@ pub enum Expression {
     Call(Box<Expression>, Vec<Expression>),
     UnboundFunction(Span, Vec<(String, Span)>, Box<Expression>),
     Function(Span, Vec<(usize, Span)>, Box<Expression>),
-    DotAccess(Box<Expression>, String)
+    DotAccess(Box<Expression>, String),
+    Index(Span, Box<Expression>, Option<Box<Expression>>) // table[index]; if the index is None, this is an anonymous append (table[] = value)
 }
 
 #[derive(Debug, Clone)]
@@ -63,6 +76,11 @@ pub struct TableEntry {
     pub label : Option<Box<Expression>>
 }
 
+#[derive(Debug, Clone)]
+pub enum CompoundOp {
+    Add, Sub, Mul, Div
+}
+
 #[derive(Debug, Clone)]
 pub enum Unary {
     Negative(Span, Box<Expression>),
@@ -0,0 +1,112 @@
+// project configuration.
+// a project can have a sitix.stx at its root. it's an ordinary [?] file; whatever it exports configures the project, e.g.
+//   [?][export port = 8000][export base_url = "/blog/"][export ignore = ["drafts", "*.psd"]][export directory_keys = "stem"]
+// every export (not just the ones sitix understands) is handed to templates as the read-only `site` table.
+// the config is read before the rest of the project is loaded, so it can't include other files.
+
+
+use std::collections::{ HashMap, BTreeMap };
+use crate::interpret::{ Data, IndexableData };
+use crate::error::*;
+use crate::utility::Span;
+
+
+pub const CONFIG_FILE : &str = "sitix.stx";
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DirectoryKeys { // what including a directory keys its table by
+    Filename, // "post.stx"
+    Stem // "post". if two files share a stem, the later one (by filename) keeps its full name
+}
+
+
+impl DirectoryKeys {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Filename => "filename",
+            Self::Stem => "stem"
+        }
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SiteConfig {
+    pub host : String, // the dev server's address
+    pub port : u16,
+    pub output : String, // where sitix static puts things, relative to the project root
+    pub base_url : String,
+    pub ignore : Vec<String>, // gitignore-style patterns for files that aren't part of the site
+    pub directory_keys : DirectoryKeys,
+    values : HashMap<String, Data> // everything the config file exported
+}
+
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            host : "0.0.0.0".to_string(),
+            port : 8080,
+            output : "output".to_string(),
+            base_url : "/".to_string(),
+            ignore : vec![],
+            directory_keys : DirectoryKeys::Filename,
+            values : HashMap::new()
+        }
+    }
+}
+
+
+impl SiteConfig {
+    pub fn from_exports(exports : HashMap<String, Data>) -> SitixResult<Self> {
+        let mut config = Self::default();
+        for (key, value) in &exports {
+            let invalid = |e : PartialError| Error::invalid_config(key, e, Span::new(0, 0, CONFIG_FILE.to_string()));
+            match key.as_str() {
+                "host" => { config.host = force_string(value).map_err(invalid)?; },
+                "port" => {
+                    let port = value.force_number().map_err(invalid)?;
+                    if port < 0.0 || port > u16::MAX as f64 || port.fract() != 0.0 {
+                        return Err(invalid(PartialError::invalid_type("port number", port)));
+                    }
+                    config.port = port as u16;
+                },
+                "output" => { config.output = force_string(value).map_err(invalid)?; },
+                "base_url" => { config.base_url = force_string(value).map_err(invalid)?; },
+                "ignore" => {
+                    config.ignore = value.clone().force_table().map_err(invalid)?.values().map(force_string).collect::<SitixPartialResult<_>>().map_err(invalid)?;
+                },
+                "directory_keys" => {
+                    config.directory_keys = match force_string(value).map_err(invalid)?.as_str() {
+                        "filename" => DirectoryKeys::Filename,
+                        "stem" => DirectoryKeys::Stem,
+                        other => { return Err(invalid(PartialError::invalid_type("\"filename\" or \"stem\"", format!("\"{}\"", other)))); }
+                    };
+                },
+                _ => {}
+            }
+        }
+        config.values = exports;
+        Ok(config)
+    }
+
+    pub fn to_data(&self) -> Data { // the `site` table. the settings sitix understands reflect any command line overrides
+        let mut table : BTreeMap<IndexableData, Data> = self.values.iter().map(|(key, value)| (IndexableData::String(key.clone()), value.clone())).collect();
+        table.insert(IndexableData::String("host".to_string()), Data::String(self.host.clone()));
+        table.insert(IndexableData::String("port".to_string()), Data::Number(self.port as f64));
+        table.insert(IndexableData::String("output".to_string()), Data::String(self.output.clone()));
+        table.insert(IndexableData::String("base_url".to_string()), Data::String(self.base_url.clone()));
+        table.insert(IndexableData::String("directory_keys".to_string()), Data::String(self.directory_keys.name().to_string()));
+        table.insert(IndexableData::String("ignore".to_string()), Data::table_from_vec(self.ignore.iter().map(|pattern| Data::String(pattern.clone())).collect()));
+        Data::Table(table)
+    }
+}
+
+
+fn force_string(data : &Data) -> SitixPartialResult<String> {
+    match data {
+        Data::String(s) | Data::Sitix(s, _) => Ok(s.clone()),
+        _ => Err(PartialError::invalid_type("string", data.typename()))
+    }
+}
@@ -19,6 +19,7 @@ Parsing Error at includer.txt:17:19:
 use crate::utility::*;
 
 
+#[derive(Clone)]
 pub struct Error {
     pub span : Span,
     pub tp : String,
@@ -76,6 +77,37 @@ impl Error {
         }
     }
 
+    pub fn undefined_variable(name : &str, suggestion : Option<String>, at : Span) -> Error {
+        Error {
+            span : at,
+            tp : "Resolution".to_string(),
+            reason : if let Some(suggestion) = suggestion {
+                format!("Undefined variable `{}`; did you mean `{}`?", name, suggestion)
+            } else {
+                format!("Undefined variable `{}`", name)
+            },
+            cause : None
+        }
+    }
+
+    pub fn filesystem(e : impl std::fmt::Display, path : String) -> Error {
+        Error {
+            span : Span::new(0, 0, path),
+            tp : "Filesystem".to_string(),
+            reason : e.to_string(),
+            cause : None
+        }
+    }
+
+    pub fn invalid_config(key : &str, e : PartialError, at : Span) -> Error {
+        Error {
+            span : at,
+            tp : "Configuration".to_string(),
+            reason : format!("Invalid value for `{}`: {}", key, e.reason),
+            cause : None
+        }
+    }
+
     pub fn bad_argument(at : Token) -> Error {
         Error {
             span : at.span,
@@ -85,21 +117,93 @@ impl Error {
         }
     }
 
-    fn debug_inner(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (line, col) = self.span.get_line_col();
-        write!(f, "{} Error at {}:{}:{}\n", self.tp, self.span.filename, line, col)?;
-        write!(f, "  {}", self.reason)?;
-        if let Some(cause) = &self.cause {
-            write!(f, "\n\nCaused By\n\n")?;
-            cause.debug_inner(f)?;
+    fn debug_inner(&self, f : &mut impl std::fmt::Write, sources : &SourceMap) -> std::fmt::Result {
+        if let Some(source) = sources.get(&self.span.filename) {
+            let (line, col) = source.line_col(self.span.start_char);
+            writeln!(f, "{} Error at {}:{}:{}", self.tp, self.span.filename, line + 1, col + 1)?;
+            writeln!(f, "  {}", self.reason)?;
+            self.snippet(f, source, line, col)?;
+        }
+        else { // no source to quote, so just say where it happened
+            writeln!(f, "{} Error at {}", self.tp, self.span.filename)?;
+            writeln!(f, "  {}", self.reason)?;
+        }
+        if let Some((cause, repeats)) = self.distinct_cause() {
+            if repeats > 0 {
+                writeln!(f, "  (and {} more times, once per level of recursion)", repeats)?;
+            }
+            write!(f, "\nCaused By\n\n")?;
+            cause.debug_inner(f, sources)?;
         }
         Ok(())
     }
 
+    fn distinct_cause(&self) -> Option<(&Error, usize)> { // the first cause that isn't this exact error again, and how many copies were
+                                                    // skipped to get there. a recursive include fails once per level, all in the same place
+        let mut cause = self.cause.as_deref()?;
+        let mut repeats = 0;
+        while let Some(next) = cause.cause.as_deref() {
+            if (&cause.tp, &cause.reason, &cause.span.filename, cause.span.start_char) != (&self.tp, &self.reason, &self.span.filename, self.span.start_char) {
+                break;
+            }
+            cause = next;
+            repeats += 1;
+        }
+        Some((cause, repeats))
+    }
+
+    fn snippet(&self, f : &mut impl std::fmt::Write, source : &SourceFile, line : usize, col : usize) -> std::fmt::Result {
+        // looks like
+        //    |
+        // 12 | [let x = y + 1]
+        //    |          ^
+        let text = source.line(line);
+        let number = (line + 1).to_string();
+        let gutter = " ".repeat(number.len());
+        let col = col.min(text.len());
+        let width = self.span.end_char.saturating_sub(self.span.start_char).min(text.len() - col).max(1); // underline the first line of the span only
+        let padding : String = text[..col].iter().map(|c| if *c == '\t' { '\t' } else { ' ' }).collect(); // tabs stay tabs so the carets line up
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, text.iter().collect::<String>())?;
+        writeln!(f, "{} | {}{}", gutter, padding, "^".repeat(width))
+    }
+
+    pub fn render(&self, sources : &SourceMap) -> String { // the full report, quoting the offending source if we have it
+        let mut out = "== ERROR ==\n\n".to_string();
+        self.debug_inner(&mut out, sources).unwrap();
+        out
+    }
+
+    pub fn render_html(&self, sources : &SourceMap) -> String { // the same report as render(), as a chunk of html for the dev server
+        let mut out = format!("<h2>{} Error</h2>\n", escape_html(&self.tp));
+        if let Some(source) = sources.get(&self.span.filename) {
+            let (line, col) = source.line_col(self.span.start_char);
+            out += &format!("<p class=\"location\">{}:{}:{}</p>\n", escape_html(&self.span.filename), line + 1, col + 1);
+            out += &format!("<p class=\"reason\">{}</p>\n", escape_html(&self.reason));
+            let mut snippet = String::new();
+            self.snippet(&mut snippet, source, line, col).unwrap();
+            out += &format!("<pre>{}</pre>\n", escape_html(&snippet));
+        }
+        else {
+            out += &format!("<p class=\"location\">{}</p>\n", escape_html(&self.span.filename));
+            out += &format!("<p class=\"reason\">{}</p>\n", escape_html(&self.reason));
+        }
+        if let Some((cause, repeats)) = self.distinct_cause() {
+            if repeats > 0 {
+                out += &format!("<p class=\"reason\">(and {} more times, once per level of recursion)</p>\n", repeats);
+            }
+            out += "<h3>Caused By</h3>\n<div class=\"cause\">\n";
+            out += &cause.render_html(sources);
+            out += "</div>\n";
+        }
+        out
+    }
+
     pub fn discard_context(self) -> PartialError {
         PartialError {
             reason : self.reason,
-            tp : self.tp
+            tp : self.tp,
+            cause : self.cause
         }
     }
 }
@@ -107,9 +211,7 @@ impl Error {
 
 impl std::fmt::Debug for Error {
     fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "== ERROR ==\n\n")?;
-        self.debug_inner(f)?;
-        Ok(())
+        write!(f, "{}", self.render(&SourceMap::new()))
     }
 }
 
@@ -127,6 +229,39 @@ impl std::error::Error for Error {}
 pub type SitixResult<T> = Result<T, Error>;
 
 
+// several errors at once, for passes that keep going after the first problem so you don't have to fix a site one typo per run
+#[derive(Debug, Clone)]
+pub struct Diagnostics(pub Vec<Error>);
+
+
+impl Diagnostics {
+    pub fn render(&self, sources : &SourceMap) -> String {
+        self.0.iter().map(|error| error.render(sources)).collect::<Vec<String>>().join("\n")
+    }
+
+    pub fn render_html(&self, sources : &SourceMap) -> String {
+        self.0.iter().map(|error| error.render_html(sources)).collect::<Vec<String>>().join("<hr>\n")
+    }
+}
+
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(&SourceMap::new()))
+    }
+}
+
+
+impl std::error::Error for Diagnostics {}
+
+
+impl From<Error> for Diagnostics {
+    fn from(e : Error) -> Self {
+        Self(vec![e])
+    }
+}
+
+
 // just the juicy bits
 // a PartialError can tell you what happened, but not where or why
 // meant to be returned by functions that don't have access to localization context (a Token-at-fault), and `weld`ed
@@ -134,7 +269,8 @@ pub type SitixResult<T> = Result<T, Error>;
 #[derive(Debug)]
 pub struct PartialError {
     pub tp : String,
-    pub reason : String
+    pub reason : String,
+    pub cause : Option<Box<Error>> // an error from somewhere else entirely, like inside an included file
 }
 
 
@@ -144,35 +280,136 @@ impl PartialError {
             tp : self.tp,
             reason : self.reason,
             span,
-            cause : None
+            cause : self.cause
         }
     }
 
     pub fn invalid_type(expected : impl std::fmt::Display, got : impl std::fmt::Display) -> PartialError {
         PartialError {
             tp : "Runtime".to_string(),
-            reason : format!("Expected a {}, got a {}", expected, got)
+            reason : format!("Expected a {}, got a {}", expected, got),
+            cause : None
         }
     }
 
     pub fn undefined_symbol() -> PartialError { // TODO: include the name of the affected symbol here
         PartialError {
             tp : "Runtime".to_string(),
-            reason : "Undefined symbol".to_string()
+            reason : "Undefined symbol".to_string(),
+            cause : None
         }
     }
 
     pub fn invalid_index(index : String) -> PartialError {
         PartialError {
             tp : "Runtime".to_string(),
-            reason : format!("Invalid index {}", index)
+            reason : format!("Invalid index {}", index),
+            cause : None
         }
     }
 
     pub fn invalid_argument_count() -> PartialError {
         PartialError {
             tp : "Runtime".to_string(),
-            reason : format!("Invalid number of arguments")
+            reason : format!("Invalid number of arguments"),
+            cause : None
+        }
+    }
+
+    pub fn include_not_found(path : &str) -> PartialError {
+        PartialError {
+            tp : "Include".to_string(),
+            reason : format!("Could not find `{}`", path),
+            cause : None
+        }
+    }
+
+    pub fn include_cycle(chain : &[String]) -> PartialError { // chain starts and ends with the same file
+        PartialError {
+            tp : "Include".to_string(),
+            reason : format!("Include cycle: {}", chain.join(" -> ")),
+            cause : None
+        }
+    }
+
+    pub fn include_depth_exceeded(max : usize, chain : &[String]) -> PartialError { // chain is every include from the page down
+        let shown = chain.len().saturating_sub(8); // the repeating part is at the end; the first couple hundred links are just noise
+        PartialError {
+            tp : "Include".to_string(),
+            reason : format!("Maximum include depth of {} exceeded: {}{}", max, if shown > 0 { "... -> " } else { "" }, chain[shown..].join(" -> ")),
+            cause : None
+        }
+    }
+
+    pub fn include_failed(path : &str, cause : Error) -> PartialError {
+        PartialError {
+            tp : "Include".to_string(),
+            reason : format!("Failed to include `{}`", path),
+            cause : Some(Box::new(cause))
+        }
+    }
+
+    pub fn invalid_emit_path(path : &str) -> PartialError {
+        PartialError {
+            tp : "Runtime".to_string(),
+            reason : format!("Cannot emit a page at `{}`; pages must stay inside the output directory", path),
+            cause : None
+        }
+    }
+
+    pub fn invalid_front_matter(line : usize, reason : &str) -> PartialError {
+        PartialError {
+            tp : "Parsing".to_string(),
+            reason : format!("Invalid front matter on line {}: {}", line, reason),
+            cause : None
+        }
+    }
+
+    pub fn emit_collision(path : &str, other : &str) -> PartialError {
+        PartialError {
+            tp : "Runtime".to_string(),
+            reason : format!("Cannot emit a page at `{}`; `{}` already writes there", path, other),
+            cause : None
+        }
+    }
+
+    pub fn not_in_config() -> PartialError {
+        PartialError {
+            tp : "Runtime".to_string(),
+            reason : format!("This builtin needs a page to work on, so it can't be used in {}", crate::config::CONFIG_FILE),
+            cause : None
+        }
+    }
+
+    pub fn read_only() -> PartialError {
+        PartialError {
+            tp : "Runtime".to_string(),
+            reason : "Cannot assign to a builtin".to_string(),
+            cause : None
+        }
+    }
+
+    pub fn invalid_assignment_target() -> PartialError {
+        PartialError {
+            tp : "Runtime".to_string(),
+            reason : "Invalid assignment target".to_string(),
+            cause : None
+        }
+    }
+
+    pub fn anonymous_index_read() -> PartialError {
+        PartialError {
+            tp : "Runtime".to_string(),
+            reason : "Cannot read from an anonymous index; `table[]` can only be assigned to".to_string(),
+            cause : None
+        }
+    }
+
+    pub fn call_depth_exceeded(max : usize) -> PartialError {
+        PartialError {
+            tp : "Runtime".to_string(),
+            reason : format!("Maximum call depth of {} exceeded (is a function recursing forever?)", max),
+            cause : None
         }
     }
 }
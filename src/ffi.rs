@@ -1,14 +1,16 @@
 // structure for handling the foreign function interface
 use std::collections::HashMap;
+use std::sync::RwLock;
 use crate::interpret::{ Data, SitixFunction, InterpreterState };
 use crate::error::*;
 use crate::filesystem::SitixProject;
+use crate::markdown;
 
 
 #[derive(Debug)]
 pub struct ForeignFunctionInterface {
     name_to_index : HashMap<String, usize>,
-    index_to_object : HashMap<usize, Data>,
+    index_to_object : RwLock<HashMap<usize, Data>>, // behind a lock so `site` can be swapped out when the dev server sees the config change
     pub top_index : usize
 }
 
@@ -17,14 +19,14 @@ impl ForeignFunctionInterface {
     pub fn new() -> Self {
         Self {
             name_to_index : HashMap::new(),
-            index_to_object : HashMap::new(),
+            index_to_object : RwLock::new(HashMap::new()),
             top_index : 0
         }
     }
 
     pub fn add(&mut self, name : String, data : Data) {
         self.name_to_index.insert(name, self.top_index);
-        self.index_to_object.insert(self.top_index, data);
+        self.index_to_object.get_mut().unwrap().insert(self.top_index, data);
         self.top_index += 1;
     }
 
@@ -32,8 +34,19 @@ impl ForeignFunctionInterface {
         Some(self.name_to_index.get(name)?.clone())
     }
 
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.name_to_index.keys()
+    }
+
     pub fn get(&self, index : usize) -> Option<Data> {
-        Some(self.index_to_object.get(&index)?.clone())
+        self.index_to_object.read().unwrap().get(&index).cloned()
+    }
+
+    pub fn replace(&self, name : &str, data : Data) { // change what an existing name refers to. its index stays the same, so code that
+                                                      // was resolved against the old value still finds the new one
+        if let Some(index) = self.name_to_index.get(name) {
+            self.index_to_object.write().unwrap().insert(*index, data);
+        }
     }
 
     pub fn add_several_functions(&mut self, to_insert : &[(String, &'static (dyn Fn(&mut InterpreterState, usize, &SitixProject, &[Data]) -> SitixPartialResult<Data> + Send + Sync))]) {
@@ -42,9 +55,17 @@ impl ForeignFunctionInterface {
         }
     }
 
+    pub fn add_config_api(&mut self) { // the standard api, minus the builtins that need a node to work on; sitix.stx is read before there are any
+        self.add_standard_api();
+        for name in ["include", "emit", "get_page_data", "set_page_data"] {
+            self.replace(name, Data::Function(SitixFunction::Builtin(&|_, _, _, _| Err(PartialError::not_in_config()))));
+        }
+    }
+
     pub fn add_standard_api(&mut self) {
         self.add_several_functions(&[
             ("print".to_string(), &|i, _, _, args| {
+                i.side_effects = true;
                 for arg in args {
                     if let Ok(data) = i.deref(arg.clone()) {
                         print!("{} ", data.to_string());
@@ -53,15 +74,59 @@ impl ForeignFunctionInterface {
                 println!("");
                 Ok(Data::Nil)
             }),
-            ("include".to_string(), &|i, node, project, args| {
-                let old_export_table = i.export_table.clone();
-                i.export_table = HashMap::new();
-                let out_node = project.search(Some(node), i.deref(args[0].clone()).unwrap().to_string()).unwrap();
-                let ret = project.into_data(out_node, i).unwrap();
-                i.export_table = old_export_table;
-                Ok(ret)
+            ("include".to_string(), &|i, node, project, args| { // include(path) or include(path, params). every entry in params is
+                                                                 // available to the included file's `param` declarations
+                let (path, params) = match args {
+                    [path] => (path, HashMap::new()),
+                    [path, params] => {
+                        let params = i.deref(params.clone())?.force_table()?;
+                        (path, params.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+                    },
+                    _ => { return Err(PartialError::invalid_argument_count()); }
+                };
+                let path = i.deref(path.clone())?.to_string();
+                let Some(out_node) = project.search(Some(node), path.clone()) else {
+                    return Err(PartialError::include_not_found(&path));
+                };
+                // a partial can include itself with different params (a tree of comments, say), so only the same file with the same params
+                // is a cycle. params with functions in them never compare equal; recursion through those is caught by the depth limit instead
+                if let Some(start) = i.include_stack.iter().position(|(n, p)| *n == out_node && *p == params) {
+                    let chain = i.include_stack[start..].iter().map(|(n, _)| n).chain([&out_node]).filter_map(|n| project.get_relative_path(*n)).collect::<Vec<String>>();
+                    return Err(PartialError::include_cycle(&chain));
+                }
+                if i.include_stack.len() >= i.max_call_depth {
+                    let chain = i.include_stack.iter().map(|(n, _)| n).chain([&out_node]).filter_map(|n| project.get_relative_path(*n)).collect::<Vec<String>>();
+                    return Err(PartialError::include_depth_exceeded(i.max_call_depth, &chain));
+                }
+                project.include(out_node, params, i).map_err(|e| PartialError::include_failed(&path, e))
+            }),
+            ("emit".to_string(), &|i, node, project, args| { // emit(path, content) writes an extra output page. relative paths are relative
+                                                              // to the emitting file's directory; a leading / means the root of the output
+                let [path, content] = args else {
+                    return Err(PartialError::invalid_argument_count());
+                };
+                let path = i.deref(path.clone())?.to_string();
+                let Some(resolved) = project.emit_path(node, &path) else {
+                    return Err(PartialError::invalid_emit_path(&path));
+                };
+                let content = i.deref(content.clone())?;
+                i.side_effects = true; // a cached include wouldn't emit anything the second time around
+                i.emitted.push((resolved, content));
+                Ok(Data::Nil)
+            }),
+            ("markdown".to_string(), &|i, _, _, args| { // markdown(text) renders markdown to html. for front matter, include the .md file instead.
+                                                        // html in the text passes straight through, so don't hand this anything you didn't write
+                let [text] = args else {
+                    return Err(PartialError::invalid_argument_count());
+                };
+                Ok(Data::String(markdown::render(&i.deref(text.clone())?.to_string())))
+            }),
+            ("no_cache".to_string(), &|i, _, _, _| { // call this in a file whose output shouldn't be reused between includes
+                i.side_effects = true;
+                Ok(Data::Nil)
             }),
             ("get_page_data".to_string(), &|i, node, project, args| {
+                i.side_effects = true; // page data changes from render to render, so anything that reads it can't be cached either
                 if let Some(pagedat) = project.get_page_data(node, args[0].to_string()) {
                     Ok(pagedat)
                 }
@@ -70,6 +135,7 @@ impl ForeignFunctionInterface {
                 }
             }),
             ("set_page_data".to_string(), &|i, node, project, args| {
+                i.side_effects = true;
                 project.set_page_data(node, args[0].to_string(), args[1].clone());
                 Ok(Data::Nil)
             }),
@@ -6,8 +6,8 @@
 // SitixRootTree
 
 
-use std::path::PathBuf;
-use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::collections::{ HashMap, HashSet, BTreeMap };
 use crate::ast::SitixExpression;
 use std::io::{Write, Read};
 use crate::resolve::ResolverState;
@@ -15,16 +15,106 @@ use crate::lexer;
 use crate::parse;
 use crate::inflate::*;
 use crate::error::*;
-use crate::interpret::{ InterpreterState, Data };
-use inotify::{ Inotify, WatchMask, WatchDescriptor };
+use crate::interpret::{ InterpreterState, Data, IndexableData };
+use crate::config::{ SiteConfig, DirectoryKeys, CONFIG_FILE };
+use crate::ignore::{ IgnoreRules, IGNORE_FILE };
+use crate::ffi::ForeignFunctionInterface;
+use crate::utility::{ SourceFile, SourceMap, Span, escape_html };
+use crate::markdown;
+use inotify::{ Inotify, Watches, WatchMask, WatchDescriptor };
 use std::sync::{ Arc, Mutex };
 
 
+pub const OUTPUT_MARKER : &str = ".sitix"; // sitix static will only write into directories that contain this file
+
+
 pub struct SitixProject {
     nodes : Vec<Node>,
     sourcedir : PathBuf,
     inotify_watches : HashMap<WatchDescriptor, usize>, // map watch descriptors to nodes.
-    page_data : Arc<Mutex<HashMap<usize, HashMap<String, Data>>>>
+    watches : Option<Watches>, // set once the inotifier is up, so nodes tracked later can be watched too
+    sources : SourceMap, // the text of every sitix file we've parsed, by project-relative path, for error reporting
+    load_errors : HashMap<String, Diagnostics>, // why each file that failed to load (or reload) did, by project-relative path. the dev
+                                                // server shows these instead of a stale render, or a 404 for a file that never loaded at all
+    rejected_sources : SourceMap, // the text of files whose latest version didn't parse. the old version stays in `sources`, since
+                                  // that's what the old syntax tree (and any runtime error it throws) points into
+    page_data : Arc<Mutex<HashMap<usize, HashMap<String, Data>>>>,
+    include_cache : Mutex<HashMap<IncludeKey, CachedInclude>>, // see include()
+    virtual_pages : Mutex<HashMap<String, usize>>, // pages made with emit(), by output-relative path, and the node that emitted them
+    pub config : SiteConfig,
+    ignore : IgnoreRules,
+    config_errors : Vec<Error>, // problems with the config file, held until load_dir can report them with everything else
+    output : PathBuf, // where sitix static writes, as an absolute path. it's often inside the project, and must never be loaded as part of it
+    config_interpreter : Option<InterpreterState> // what ran the config. `site` can hold functions, which need it alive to keep their variables
+}
+
+
+pub enum RenderAction { // what render_node did with a node
+    Rendered,
+    Copied,
+    Nothing // directories and [?] files don't produce an output file of their own
+}
+
+
+#[derive(Debug, Default)]
+pub struct RenderSummary { // the outcome of a static build. paths are output paths
+    pub rendered : Vec<PathBuf>,
+    pub copied : Vec<PathBuf>,
+    pub failed : Vec<(PathBuf, Box<dyn std::error::Error + Send + Sync>)>
+}
+
+
+impl RenderSummary {
+    pub fn succeeded(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+
+type IncludeKey = (usize, Vec<(String, ParamKey)>); // the node, and its params sorted by name
+
+
+struct CachedInclude { // the result of including a node with some parameters
+    result : Data,
+    dependencies : HashSet<usize> // every node that went into the result, so a cache hit still tells live reload what the page depends on
+}
+
+
+#[derive(PartialEq, Eq, Hash)]
+enum ParamKey { // a hashable copy of an include parameter, for looking up cached includes
+    Boolean(bool),
+    Nil,
+    Number(u64), // the bits of the f64
+    String(String),
+    Sitix(String, Vec<(String, ParamKey)>),
+    Table(Vec<(IndexableData, ParamKey)>),
+    Bytes(Vec<u8>)
+}
+
+
+impl ParamKey {
+    fn from_data(data : &Data) -> Option<ParamKey> { // None for functions (and anything containing one), which can't be compared
+        Some(match data {
+            Data::Boolean(b) => Self::Boolean(*b),
+            Data::Nil => Self::Nil,
+            Data::Number(n) => Self::Number(n.to_bits()),
+            Data::String(s) => Self::String(s.clone()),
+            Data::Sitix(s, exports) => {
+                let mut exports = exports.iter().map(|(name, value)| Some((name.clone(), Self::from_data(value)?))).collect::<Option<Vec<_>>>()?;
+                exports.sort_by(|a, b| a.0.cmp(&b.0));
+                Self::Sitix(s.clone(), exports)
+            },
+            Data::Table(table) => Self::Table(table.iter().map(|(key, value)| Some((key.clone(), Self::from_data(value)?))).collect::<Option<_>>()?),
+            Data::Bytes(bytes) => Self::Bytes(bytes.clone()),
+            Data::Function(_) | Data::VariableHandle(_) => { return None; }
+        })
+    }
+
+    fn from_params(params : &HashMap<String, Data>) -> Option<Vec<(String, ParamKey)>> {
+        let mut key = params.iter().map(|(name, value)| Some((name.clone(), Self::from_data(value)?))).collect::<Option<Vec<_>>>()?;
+        key.sort_by(|a, b| a.0.cmp(&b.0));
+        Some(key)
+    }
 }
 
 
@@ -46,20 +136,118 @@ pub enum Node {
         parent : Option<usize>,
         source_path_abs : PathBuf, // absolute source path
     },
+    MarkdownFile { // a .md file with no opening phrase. including one gives you its front matter and rendered body; it's copied to the output as-is
+        name : String,
+        parent : Option<usize>,
+        source_path_abs : PathBuf
+    },
     Deleted // TODO: make this not awful
 }
 
 
 impl SitixProject {
     pub fn new(sourcedir : PathBuf) -> Self {
-        Self {
+        let mut project = Self {
             nodes : vec![],
             sourcedir,
             inotify_watches : HashMap::new(),
-            page_data : Arc::new(Mutex::new(HashMap::new()))
+            watches : None,
+            sources : SourceMap::new(),
+            load_errors : HashMap::new(),
+            rejected_sources : SourceMap::new(),
+            page_data : Arc::new(Mutex::new(HashMap::new())),
+            include_cache : Mutex::new(HashMap::new()),
+            virtual_pages : Mutex::new(HashMap::new()),
+            config : SiteConfig::default(),
+            ignore : IgnoreRules::default(),
+            config_errors : vec![],
+            output : PathBuf::new(),
+            config_interpreter : None
+        };
+        project.load_config();
+        project.output = std::path::absolute(project.sourcedir.join(&project.config.output)).unwrap(); // the config's output is relative to the project
+        project.load_ignore_rules();
+        project
+    }
+
+    fn load_ignore_rules(&mut self) { // must come after load_config, since the config can have ignore patterns too
+        self.ignore.add_patterns(self.config.ignore.iter().map(String::as_str));
+        let path = self.sourcedir.join(IGNORE_FILE);
+        if path.is_file() {
+            match std::fs::read_to_string(&path) {
+                Ok(rules) => { self.ignore.add_patterns(rules.lines()); },
+                Err(e) => { self.config_errors.push(Error::filesystem(e, IGNORE_FILE.to_string())); }
+            }
+        }
+    }
+
+    pub fn is_ignored(&self, parent : Option<usize>, name : &str) -> bool {
+        let path = if let Some(parent) = parent { self.get_src_path(parent).unwrap() } else { self.sourcedir.clone() }.join(name);
+        self.is_ignored_path(&path)
+    }
+
+    fn is_ignored_path(&self, path : &Path) -> bool { // ignore rules, plus anything we've written (or would write) output into
+        self.ignore.is_ignored(Path::new(&self.relative_name(path)), path.is_dir()) || self.is_output(path)
+    }
+
+    fn is_output(&self, path : &Path) -> bool {
+        path.join(OUTPUT_MARKER).is_file() || std::path::absolute(path).is_ok_and(|path| path == self.output)
+    }
+
+    pub fn set_output(&mut self, output : String) { // --output, which (unlike the config's) is relative to the working directory
+        self.output = std::path::absolute(&output).unwrap();
+        self.config.output = output;
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    fn load_config(&mut self) {
+        match self.read_config() {
+            Ok(Some(config)) => { self.config = config; },
+            Ok(None) => {},
+            Err(e) => { self.config_errors.push(e); }
         }
     }
 
+    fn read_config(&mut self) -> SitixResult<Option<SiteConfig>> { // read sitix.stx, if there is one
+        let path = self.sourcedir.join(CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let mut ffi = ForeignFunctionInterface::new();
+        ffi.add_config_api();
+        let ffi = Arc::new(ffi);
+        let Ok(expr) = self.parse_file(path, &mut ResolverState::new(ffi.clone())) else {
+            return Ok(None); // the config file is loaded along with the rest of the project, which will report this better than we could
+        };
+        let mut interpreter = InterpreterState::new(ffi);
+        // there's no node for the config yet, so the builtins that need one (include, emit...) are swapped out for ones that fail politely
+        let data = expr.interpret(&mut interpreter, usize::MAX, self);
+        self.config_interpreter = Some(interpreter);
+        match data? {
+            Data::Sitix(_, exports) => SiteConfig::from_exports(exports).map(Some),
+            _ => Ok(Some(SiteConfig::default()))
+        }
+    }
+
+    pub fn reload_config(&mut self) -> SitixResult<()> { // for the dev server, when sitix.stx changes. the address it's listening on and the
+                                                         // ignore rules were settled at startup, so those stay as they are
+        let Some(mut config) = self.read_config()? else {
+            return Ok(());
+        };
+        config.host = self.config.host.clone();
+        config.port = self.config.port;
+        config.ignore = self.config.ignore.clone();
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn is_config(&self, node : usize) -> bool {
+        self.get_parent(node).is_none() && self.get_name(node).as_deref() == Some(CONFIG_FILE)
+    }
+
     pub fn get_path(&self, id : usize, root : PathBuf) -> Option<PathBuf> { // perform recursive lookups to transform a given node id into its filename
         let mut root = if let Some(parent) = self.get_parent(id) { // find what comes *before* this name
             self.get_path(parent, root)?
@@ -75,11 +263,20 @@ impl SitixProject {
         self.get_path(id, self.sourcedir.clone())
     }
 
+    pub fn get_relative_path(&self, id : usize) -> Option<String> { // for showing to humans
+        Some(self.relative_name(&self.get_src_path(id)?))
+    }
+
+    pub fn modified(&self, id : usize) -> Option<std::time::SystemTime> { // when a node's source last changed on disk
+        std::fs::metadata(self.get_src_path(id)?).ok()?.modified().ok()
+    }
+
     pub fn get_name(&self, id : usize) -> Option<String> {
         Some(match self.nodes.get(id)? {
             Node::Directory { name, .. } => name.clone(),
             Node::ObjectFile { name, .. } => name.clone(),
             Node::DataFile { name, .. } => name.clone(),
+            Node::MarkdownFile { name, .. } => name.clone(),
             Node::Deleted => { return None; }
         })
     }
@@ -89,6 +286,7 @@ impl SitixProject {
             Node::Directory { parent, .. } => parent,
             Node::ObjectFile { parent, .. } => parent,
             Node::DataFile { parent, .. } => parent,
+            Node::MarkdownFile { parent, .. } => parent,
             Node::Deleted => { return None; }
         }.clone()
     }
@@ -101,10 +299,22 @@ impl SitixProject {
         }
     }
 
-    pub fn load_dir(&mut self, childof : Option<usize>, resolver : &mut ResolverState) -> Result<(), Box<dyn std::error::Error>> { // recursively load a source directory
+    pub fn load_dir(&mut self, childof : Option<usize>, resolver : &mut ResolverState) -> Result<(), Diagnostics> { // recursively load a source directory
+                                                                                                                 // a broken file doesn't stop the rest of the project from loading;
+                                                                                                                 // every error is collected and returned at the end
         let root = if let Some(childof) = childof { self.get_src_path(childof).unwrap() } else { self.sourcedir.clone() };
-        for child in std::fs::read_dir(root).unwrap() {
-            let child = child.unwrap();
+        let mut errors = if childof.is_none() { std::mem::take(&mut self.config_errors) } else { vec![] };
+        for child in std::fs::read_dir(&root).map_err(|e| Error::filesystem(e, self.relative_name(&root)))? {
+            let child = match child {
+                Ok(child) => child,
+                Err(e) => {
+                    errors.push(Error::filesystem(e, self.relative_name(&root)));
+                    continue;
+                }
+            };
+            if self.is_ignored_path(&child.path()) {
+                continue;
+            }
             let id;
             if child.path().is_dir() {
                 self.nodes.push(Node::Directory {
@@ -113,32 +323,57 @@ impl SitixProject {
                     children : vec![]
                 });
                 id = self.nodes.len() - 1;
-                self.load_dir(Some(id), resolver)?;
+                if let Err(Diagnostics(e)) = self.load_dir(Some(id), resolver) {
+                    errors.extend(e);
+                }
             }
             else {
-                self.nodes.push(self.load_file(childof, child.path(), resolver)?);
-                id = self.nodes.len() - 1;
+                match self.load_file(childof, child.path(), resolver) {
+                    Ok(node) => {
+                        self.nodes.push(node);
+                        id = self.nodes.len() - 1;
+                    },
+                    Err(Diagnostics(e)) => {
+                        errors.extend(e);
+                        continue;
+                    }
+                }
             }
             if let Some(childof) = childof {
                 self.setchild(id, childof);
             }
         }
-        Ok(())
+        if errors.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(Diagnostics(errors))
+        }
+    }
+
+    fn load_file(&mut self, parent : Option<usize>, path : PathBuf, resolver : &mut ResolverState) -> Result<Node, Diagnostics> {
+        let name = self.relative_name(&path);
+        let node = self.read_node(parent, path, resolver);
+        match &node {
+            Ok(_) => { self.load_errors.remove(&name); },
+            Err(e) => { self.load_errors.insert(name, e.clone()); }
+        }
+        node
     }
 
-    fn load_file(&self, parent : Option<usize>, path : PathBuf, resolver : &mut ResolverState) -> Result<Node, Box<dyn std::error::Error>> {
+    fn read_node(&mut self, parent : Option<usize>, path : PathBuf, resolver : &mut ResolverState) -> Result<Node, Diagnostics> {
         let mut opening_phrase = [0u8; 3];
         let count;
         {
-            let mut file = std::fs::File::open(&path)?;
-            count = file.read(&mut opening_phrase)?;
+            let mut file = std::fs::File::open(&path).map_err(|e| Error::filesystem(e, self.relative_name(&path)))?;
+            count = file.read(&mut opening_phrase).map_err(|e| Error::filesystem(e, self.relative_name(&path)))?;
         }
         if count == 3 {
             if opening_phrase[0] == b'[' && opening_phrase[2] == b']' {
                 match opening_phrase[1] {
                     b'!' | b'?' => {
                         return Ok(Node::ObjectFile {
-                            expr : Self::parse_file(path.clone(), resolver)?,
+                            expr : self.parse_file(path.clone(), resolver)?,
                             name : path.file_name().unwrap().to_str().unwrap().to_string(),
                             render : opening_phrase[1] == b'!',
                             parent
@@ -148,6 +383,13 @@ impl SitixProject {
                 }
             }
         }
+        if markdown::is_markdown(&path) {
+            return Ok(Node::MarkdownFile {
+                source_path_abs : std::path::absolute(&path).unwrap(),
+                name : path.file_name().unwrap().to_str().unwrap().to_string(),
+                parent
+            });
+        }
         Ok(Node::DataFile {
             source_path_abs : std::path::absolute(&path).unwrap(),
             name : path.file_name().unwrap().to_str().unwrap().to_string(),
@@ -155,56 +397,328 @@ impl SitixProject {
         })
     }
 
-    pub fn track_file(&mut self, parent : Option<usize>, name : &str, resolver : &mut ResolverState) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn track_file(&mut self, parent : Option<usize>, name : &str, resolver : &mut ResolverState) -> Result<(), Diagnostics> { // start tracking a file or directory
+                                                                                                                                  // that appeared after the project was loaded
+        if self.is_ignored(parent, name) {
+            return Ok(());
+        }
+        if let Some(existing) = self.tracked(parent, name) { // we can hear about the same file twice if it showed up while its directory was being loaded
+            return self.reload(existing, resolver);
+        }
         let mut path = if let Some(parent) = parent { self.get_src_path(parent).unwrap() } else { self.sourcedir.clone() };
         path.push(name);
-        let file = self.load_file(parent, path, resolver)?;
-        self.nodes.push(file);
+        let first_new = self.nodes.len();
+        let result = if path.is_dir() {
+            self.nodes.push(Node::Directory {
+                name : name.to_string(),
+                parent,
+                children : vec![]
+            });
+            if let Some(parent) = parent {
+                self.setchild(first_new, parent);
+            }
+            self.watch(first_new)?; // watch before loading, so nothing created in the meantime slips through the cracks
+            self.load_dir(Some(first_new), resolver)
+        }
+        else {
+            let file = self.load_file(parent, path, resolver)?;
+            self.nodes.push(file);
+            if let Some(parent) = parent {
+                self.setchild(first_new, parent);
+            }
+            self.watch(first_new)?;
+            Ok(())
+        };
+        for node in first_new + 1..self.nodes.len() { // everything that turned up inside a new directory
+            self.watch(node)?;
+        }
+        result
+    }
+
+    pub fn load_error(&self, url : &str) -> Option<(&Diagnostics, HashSet<usize>)> { // why the file behind a url didn't load, if it didn't,
+                                                                                     // and the nodes whose changes should reload the error page
+        let mut name = url.trim_start_matches('/').to_string();
+        if name.is_empty() || name.ends_with('/') {
+            name += "index.html";
+        }
+        let e = self.load_errors.get(&name).filter(|_| self.sourcedir.join(&name).is_file())?; // it might have been deleted since
+        let mut dependencies = HashSet::new();
+        dependencies.extend(self.search(None, "/".to_string() + &name)); // a reload that failed; the node is still around
+        if let Some((parent, _)) = name.rsplit_once('/') { // a file that never loaded. fixing it makes the watcher track it, which its
+                                                           // directory hears about (at the root, every page reloads)
+            dependencies.extend(self.search(None, "/".to_string() + parent));
+        }
+        Some((e, dependencies))
+    }
+
+    pub fn tracked(&self, parent : Option<usize>, name : &str) -> Option<usize> { // unlike child_get, this only looks at the top level when parent is None
+        (0..self.nodes.len()).find(|node| self.get_parent(*node) == parent && self.get_name(*node).as_deref() == Some(name))
+    }
+
+    pub fn reload(&mut self, node : usize, resolver : &mut ResolverState) -> Result<(), Diagnostics> { // re-read a file that changed on disk, keeping its node id.
+                                                                                                       // if the new version doesn't parse, the old one stays put
+        let (Node::ObjectFile { parent, .. } | Node::DataFile { parent, .. } | Node::MarkdownFile { parent, .. }) = self.nodes[node] else {
+            return Ok(()); // directories have nothing to reload, and deleted nodes are gone for good
+        };
+        let file = self.load_file(parent, self.get_src_path(node).unwrap(), resolver)?;
+        self.nodes[node] = file;
         Ok(())
     }
 
-    fn parse_file(path : PathBuf, resolver : &mut ResolverState) -> Result<SitixExpression, Box<dyn std::error::Error>> {
-        let file = lexer::FileReader::open(&path);
+    fn relative_name(&self, path : &std::path::Path) -> String { // spans use the project-relative path, which (unlike the bare filename) is unique
+        path.strip_prefix(&self.sourcedir).unwrap_or(path).to_string_lossy().to_string()
+    }
+
+    fn parse_file(&mut self, path : PathBuf, resolver : &mut ResolverState) -> Result<SitixExpression, Diagnostics> {
+        let name = self.relative_name(&path);
+        let source = std::fs::read_to_string(&path).map_err(|e| Error::filesystem(e, name.clone()))?;
+        let ast = Self::parse_source(&name, &source, path.file_name().unwrap().to_str().unwrap().to_string(), resolver);
+        if ast.is_ok() { // only swap the new text in once it parses; until then, the old syntax tree is still the one being run
+            self.rejected_sources.remove(&name);
+            self.sources.insert(name, SourceFile::new(&source));
+        }
+        else {
+            self.rejected_sources.insert(name, SourceFile::new(&source));
+        }
+        ast
+    }
+
+    fn parse_source(name : &str, source : &str, filename : String, resolver : &mut ResolverState) -> Result<SitixExpression, Diagnostics> {
+        let file = lexer::FileReader::new(name.to_string(), source);
         let tokens = lexer::lexer(file)?;
 
         let mut token_buffer = parse::TokenReader::new(tokens);
         let mut inflated = SitixTree::root(&mut token_buffer)?;
 
-        let ast = inflated.parse(Some(path.file_name().unwrap().to_str().unwrap().to_string()))?;
+        let ast = inflated.parse_root(filename)?;
 
         let ast = ast.resolve(resolver);
-        resolver.seal();
-        Ok(ast)
-    }
-
-    fn render_node(&self, out : PathBuf, node_index : usize, i : &mut InterpreterState) -> Result<(), Box<dyn std::error::Error>> {
-        let path = self.get_path(node_index, out).unwrap();
-        if let Some(node) = self.nodes.get(node_index) {
-            match node {
-                Node::Directory { .. } => {
-                    std::fs::create_dir_all(path)?;
-                },
-                Node::ObjectFile { expr, render, .. } => {
-                    if *render {
-                        let mut file = std::fs::File::create(path)?;
-                        file.write_all(expr.interpret(i, node_index, self)?.to_string().as_bytes()).unwrap();
+        resolver.seal(); // even if resolution failed, so the next file starts with a clean symbol table
+        ast
+    }
+
+    fn render_node(&self, path : &PathBuf, node_index : usize, i : &mut InterpreterState) -> Result<RenderAction, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(match &self.nodes[node_index] {
+            Node::Directory { .. } => {
+                std::fs::create_dir_all(path)?;
+                RenderAction::Nothing
+            },
+            Node::ObjectFile { render, .. } => {
+                if *render {
+                    let data = self.into_data(node_index, i)?; // interpret first, so a broken template doesn't leave an empty file behind
+                    let mut file = std::fs::File::create(path)?;
+                    file.write_all(data.to_string().as_bytes())?;
+                    RenderAction::Rendered
+                }
+                else {
+                    RenderAction::Nothing
+                }
+            },
+            Node::DataFile { source_path_abs, .. } | Node::MarkdownFile { source_path_abs, .. } => {
+                std::fs::copy(source_path_abs, path)?;
+                RenderAction::Copied
+            },
+            Node::Deleted => panic!("unreachable")
+        })
+    }
+
+    pub fn render(&self, out : PathBuf, i : &mut InterpreterState, keep_going : bool) -> RenderSummary { // if keep_going is false, stop at the first failure
+        let mut summary = RenderSummary::default();
+        self.clear_include_cache();
+        self.clear_virtual_pages();
+        for node in 0..self.nodes.len() {
+            let path = self.get_path(node, out.clone()).unwrap();
+            i.emitted.clear();
+            match self.render_node(&path, node, i) {
+                Ok(RenderAction::Rendered) => summary.rendered.push(path.clone()),
+                Ok(RenderAction::Copied) => summary.copied.push(path.clone()),
+                Ok(RenderAction::Nothing) => {},
+                Err(e) => {
+                    summary.failed.push((path, e));
+                    if !keep_going {
+                        break;
                     }
-                },
-                Node::DataFile { source_path_abs, .. } => {
-                    std::fs::copy(source_path_abs, path).unwrap();
-                },
-                Node::Deleted => panic!("unreachable")
+                    continue;
+                }
+            }
+            if let Err(e) = self.record_emitted(node, &i.emitted) {
+                summary.rendered.retain(|rendered| *rendered != path);
+                summary.failed.push((path, e.into()));
+                if !keep_going {
+                    break;
+                }
+                continue;
+            }
+            for (emitted, content) in std::mem::take(&mut i.emitted) {
+                let path = out.join(emitted);
+                match Self::write_output(&path, &content) {
+                    Ok(()) => summary.rendered.push(path),
+                    Err(e) => summary.failed.push((path, e.into()))
+                }
+            }
+            if !keep_going && !summary.failed.is_empty() {
+                break;
+            }
+        }
+        summary
+    }
+
+    fn write_output(path : &Path, content : &Data) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        match content {
+            Data::Bytes(bytes) => std::fs::write(path, bytes),
+            content => std::fs::write(path, content.to_string())
+        }
+    }
+
+    pub fn emit_path(&self, node : usize, path : &str) -> Option<String> { // where an emit() from a node ends up, relative to the output directory.
+                                                                         // None if it would escape the output directory
+        let mut parts : Vec<String> = if path.starts_with('/') { vec![] } else {
+            self.get_parent(node).and_then(|parent| self.get_relative_path(parent)).map_or(vec![], |dir| dir.split('/').map(str::to_string).collect())
+        };
+        for part in path.split('/') {
+            match part {
+                "" | "." => {},
+                ".." => { parts.pop()?; },
+                part => parts.push(part.to_string())
+            }
+        }
+        if parts.is_empty() {
+            return None;
+        }
+        Some(parts.join("/"))
+    }
+
+    pub fn record_emitted(&self, node : usize, emitted : &[(String, Data)]) -> SitixResult<()> { // remember which pages a node emits, replacing
+                                                                                                // whatever it emitted last time. two nodes (or
+                                                                                                // two emits) writing the same page is an error
+        let mut virtual_pages = self.virtual_pages.lock().unwrap();
+        virtual_pages.retain(|_, n| *n != node);
+        for (path, _) in emitted {
+            if let Some(other) = self.output_node(path).or_else(|| virtual_pages.get(path).copied()) {
+                let other = self.get_relative_path(other).unwrap_or_default();
+                return Err(PartialError::emit_collision(path, &other).weld(Span::new(0, 0, self.get_relative_path(node).unwrap_or_default())));
             }
+            virtual_pages.insert(path.clone(), node);
         }
         Ok(())
     }
 
-    pub fn render(&self, out : PathBuf, i : &mut InterpreterState) {
+    pub fn clear_virtual_pages(&self) {
+        self.virtual_pages.lock().unwrap().clear();
+    }
+
+    fn output_node(&self, path : &str) -> Option<usize> { // the node whose own output lands at an output-relative path
+        (0..self.nodes.len()).find(|node| !matches!(self.nodes[*node], Node::ObjectFile { render : false, .. } | Node::Deleted)
+            && self.get_relative_path(*node).as_deref() == Some(path))
+    }
+
+    pub fn find_virtual_page(&self, path : &str) -> Option<usize> {
+        self.virtual_pages.lock().unwrap().get(path).copied()
+    }
+
+    pub fn is_markdown(&self, node : usize) -> bool {
+        matches!(self.nodes.get(node), Some(Node::MarkdownFile { .. }))
+    }
+
+    pub fn is_sitix(&self, node : usize) -> bool { // whether serving this node means running a template, as opposed to sending a file as-is
+        matches!(self.nodes.get(node), Some(Node::ObjectFile { .. }))
+    }
+
+    pub fn renderable(&self) -> Vec<usize> { // every [!] file
+        (0..self.nodes.len()).filter(|node| matches!(self.nodes[*node], Node::ObjectFile { render : true, .. })).collect()
+    }
+
+    pub fn prepare_output(out : &Path, force : bool) -> SitixResult<()> { // make sure we're allowed to write into an output directory.
+                                                                       // a directory is fair game if it's new, empty, or has our marker in it;
+                                                                       // anything else might be someone's project, so we only touch it if forced.
+        let name = out.display().to_string();
+        let marker = out.join(OUTPUT_MARKER);
+        if out.exists() {
+            if !out.is_dir() {
+                return Err(Error::filesystem("the output path exists and is not a directory", name));
+            }
+            let empty = std::fs::read_dir(out).map_err(|e| Error::filesystem(e, name.clone()))?.next().is_none();
+            if !empty && !marker.exists() && !force {
+                return Err(Error::filesystem(format!("refusing to write into a directory that does not contain a {} file; pass --force if you're sure", OUTPUT_MARKER), name));
+            }
+        }
+        else {
+            std::fs::create_dir_all(out).map_err(|e| Error::filesystem(e, name.clone()))?;
+        }
+        std::fs::write(&marker, "this directory is generated by sitix static. its contents may be overwritten or deleted.\n").map_err(|e| Error::filesystem(e, name))
+    }
+
+    pub fn clean(&self, out : &Path, keep : &[PathBuf]) -> std::io::Result<Vec<PathBuf>> { // remove everything in the output directory that no node would produce,
+                                                                                         // other than `keep` (pages emitted during the build).
+                                                                                         // returns the paths that were removed.
+        let mut expected = HashSet::new();
+        expected.insert(out.join(OUTPUT_MARKER));
+        for path in keep {
+            expected.extend(path.ancestors().take_while(|ancestor| *ancestor != out).map(Path::to_path_buf));
+        }
         for node in 0..self.nodes.len() {
-            if let Err(e) = self.render_node(out.clone(), node, i) {
-                println!("{}", e);
+            match &self.nodes[node] {
+                Node::ObjectFile { render : false, .. } | Node::Deleted => {},
+                _ => {
+                    expected.insert(self.get_path(node, out.to_path_buf()).unwrap());
+                }
+            }
+        }
+        let mut removed = vec![];
+        Self::clean_dir(out, &expected, &mut removed)?;
+        Ok(removed)
+    }
+
+    fn clean_dir(dir : &Path, expected : &HashSet<PathBuf>, removed : &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !expected.contains(&path) {
+                if path.is_dir() {
+                    std::fs::remove_dir_all(&path)?;
+                }
+                else {
+                    std::fs::remove_file(&path)?;
+                }
+                removed.push(path);
+            }
+            else if path.is_dir() {
+                Self::clean_dir(&path, expected, removed)?;
             }
         }
+        Ok(())
+    }
+
+    pub fn report(&self, e : &(dyn std::error::Error + 'static)) -> String { // format an error for humans, quoting the source if it's one of ours
+        if let Some(e) = e.downcast_ref::<Error>() {
+            e.render(&self.sources)
+        }
+        else if let Some(e) = e.downcast_ref::<Diagnostics>() {
+            e.render(&self.load_sources())
+        }
+        else {
+            e.to_string()
+        }
+    }
+
+    pub fn report_html(&self, e : &(dyn std::error::Error + 'static)) -> String { // report(), but for the dev server's error page
+        if let Some(e) = e.downcast_ref::<Error>() {
+            e.render_html(&self.sources)
+        }
+        else if let Some(e) = e.downcast_ref::<Diagnostics>() {
+            e.render_html(&self.load_sources())
+        }
+        else {
+            format!("<h2>Error</h2>\n<p class=\"reason\">{}</p>\n", escape_html(&e.to_string()))
+        }
+    }
+
+    fn load_sources(&self) -> SourceMap { // load errors (Diagnostics) point into the version of the file that failed, not the one in use
+        let mut sources = self.sources.clone();
+        sources.extend(self.rejected_sources.clone());
+        sources
     }
 
     fn find_uphill(&self, from : Option<usize>, name : &str) -> Option<usize> { // from must be the PARENT of the node we're walking up from
@@ -258,34 +772,107 @@ impl SitixProject {
     }
 
     pub fn into_data(&self, node : usize, i : &mut InterpreterState) -> SitixResult<Data> {
+        i.dependencies.insert(node);
+        i.include_stack.push((node, i.include_params.clone()));
+        let ret = self.node_data(node, i);
+        i.include_stack.pop();
+        ret
+    }
+
+    fn node_data(&self, node : usize, i : &mut InterpreterState) -> SitixResult<Data> {
         Ok(match self.nodes.get(node).unwrap() {
-            Node::Directory { children, .. } => {
-                let mut to_vec = vec![];
-                for child in children {
-                    to_vec.push(self.into_data(*child, i)?);
+            Node::Directory { children, .. } => { // a table keyed by filename. tables are ordered by key, so iterating over one
+                                                  // goes through the directory in filename order no matter what the filesystem says
+                let mut children : Vec<(String, usize)> = children.iter().map(|child| (self.get_name(*child).unwrap(), *child)).collect();
+                children.sort();
+                let mut table = BTreeMap::new();
+                for (name, child) in children {
+                    let stem = Path::new(&name).file_stem().map(|stem| stem.to_string_lossy().to_string());
+                    let key = match stem {
+                        Some(stem) if self.config.directory_keys == DirectoryKeys::Stem && !table.contains_key(&IndexableData::String(stem.clone())) => stem,
+                        _ => name
+                    };
+                    table.insert(IndexableData::String(key), self.into_data(child, i)?);
                 }
-                Data::table_from_vec(to_vec)
+                Data::Table(table)
             },
             Node::ObjectFile { expr, .. } => {
                 expr.interpret(i, node, self)?
             },
             Node::DataFile { source_path_abs, .. } => {
-                Data::String(std::fs::read_to_string(source_path_abs).unwrap())
+                let bytes = std::fs::read(source_path_abs).map_err(|e| Error::filesystem(e, self.relative_name(source_path_abs)))?;
+                match String::from_utf8(bytes) {
+                    Ok(text) => Data::String(text),
+                    Err(e) => Data::Bytes(e.into_bytes()) // binary files stay binary; templates can still pass them around
+                }
+            },
+            Node::MarkdownFile { source_path_abs, name, .. } => {
+                let relative = self.relative_name(source_path_abs);
+                let text = std::fs::read_to_string(source_path_abs).map_err(|e| Error::filesystem(e, relative.clone()))?;
+                let mut document = markdown::document(&text).map_err(|e| e.weld(Span::new(0, 0, relative)))?;
+                document.insert(IndexableData::String("__filename__".to_string()), Data::String(name.clone()));
+                Data::Table(document)
             },
             Node::Deleted => panic!("unreachable")
         })
     }
 
-    pub fn setup_inotifier(&mut self) -> Inotify { // build an inotify watch tree by visiting every node
+    pub fn include(&self, node : usize, params : HashMap<String, Data>, i : &mut InterpreterState) -> SitixResult<Data> { // into_data for the include builtin.
+        // most includes are layouts and partials that come out the same every time, so results are cached for the length of a render:
+        // the whole of sitix static, or one request for sitix dev (see clear_include_cache). anything that calls a builtin with side effects
+        // (set_page_data, print, no_cache...) while being included isn't cached, and neither is anything that includes it. neither are
+        // includes with functions in their params, since there's no telling whether two functions are the same.
+        let key = ParamKey::from_params(&params).map(|params| (node, params));
+        if let Some(cached) = key.as_ref().and_then(|key| self.include_cache.lock().unwrap().get(key).map(|cached| (cached.result.clone(), cached.dependencies.clone()))) {
+            i.dependencies.extend(&cached.1);
+            return Ok(cached.0);
+        }
+        let old_export_table = std::mem::take(&mut i.export_table);
+        let old_params = std::mem::replace(&mut i.include_params, params.clone());
+        let old_side_effects = std::mem::replace(&mut i.side_effects, false);
+        let old_dependencies = std::mem::take(&mut i.dependencies);
+        let ret = self.into_data(node, i);
+        let dependencies = std::mem::replace(&mut i.dependencies, old_dependencies);
+        i.dependencies.extend(&dependencies);
+        let side_effects = std::mem::replace(&mut i.side_effects, old_side_effects);
+        i.side_effects |= side_effects;
+        i.export_table = old_export_table;
+        i.include_params = old_params;
+        let ret = ret?;
+        if let (Some(key), false) = (key, side_effects) {
+            self.include_cache.lock().unwrap().insert(key, CachedInclude {
+                result : ret.clone(),
+                dependencies
+            });
+        }
+        Ok(ret)
+    }
+
+    pub fn clear_include_cache(&self) { // call at the start of every render. cached results can hold functions that close over the
+                                        // interpreter that made them, so they mustn't outlive it
+        self.include_cache.lock().unwrap().clear();
+    }
+
+    pub fn setup_inotifier(&mut self) -> Inotify { // build an inotify watch tree by visiting every node.
+                                                   // ignored files never become nodes, so they don't get watched
         let inotify = Inotify::init().unwrap();
-        inotify.watches().add(&self.sourcedir, WatchMask::CREATE | WatchMask::MOVED_TO | WatchMask::MOVED_FROM).expect("failed to set up file watcher");
+        inotify.watches().add(&self.sourcedir, WatchMask::CREATE | WatchMask::MOVED_TO | WatchMask::MOVED_FROM | WatchMask::CLOSE_WRITE).expect("failed to set up file watcher");
+        self.watches = Some(inotify.watches());
         for node_index in 0..self.nodes.len() {
-            let watch = inotify.watches().add(self.get_src_path(node_index).unwrap(), WatchMask::ALL_EVENTS).expect("failed to set up file watcher");
-            self.inotify_watches.insert(watch, node_index);
+            self.watch(node_index).expect("failed to set up file watcher");
         }
         inotify
     }
 
+    fn watch(&mut self, node : usize) -> SitixResult<()> { // does nothing if the inotifier hasn't been set up yet
+        let (Some(mut watches), Some(path)) = (self.watches.clone(), self.get_src_path(node)) else {
+            return Ok(());
+        };
+        let watch = watches.add(&path, WatchMask::ALL_EVENTS).map_err(|e| Error::filesystem(e, self.relative_name(&path)))?;
+        self.inotify_watches.insert(watch, node);
+        Ok(())
+    }
+
     pub fn search_watch_descriptor(&self, wd : &WatchDescriptor) -> Option<usize> {
         self.inotify_watches.get(wd).copied()
     }
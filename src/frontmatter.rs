@@ -0,0 +1,89 @@
+// front matter: the metadata block at the top of a markdown file.
+//   ---                    +++
+//   title: First Post      title = "First Post"
+//   tags: [rust, sitix]    tags = ["rust", "sitix"]
+//   ---                    +++
+// yaml goes between --- lines, toml between +++ lines. mappings and tables become sitix tables keyed by string; lists become tables
+// keyed 0, 1, 2... toml dates come out as strings.
+
+
+use std::collections::BTreeMap;
+use crate::interpret::{ Data, IndexableData };
+use crate::error::*;
+
+
+pub fn split(text : &str) -> Option<(&str, bool, &str)> { // separate the front matter from the body: (front matter, is it toml, body)
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let first_line = text.lines().next()?;
+    let (fence, toml) = match first_line.trim_end() {
+        "---" => ("---", false),
+        "+++" => ("+++", true),
+        _ => { return None; }
+    };
+    let rest = &text[first_line.len()..];
+    let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n'))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == fence || (!toml && line.trim_end() == "...") {
+            return Some((&rest[..offset], toml, &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None // never closed, so it isn't front matter after all
+}
+
+
+pub fn parse(text : &str, toml : bool) -> SitixPartialResult<BTreeMap<IndexableData, Data>> { // text is front matter from split(). errors count lines
+                                                                                          // from the top of the file, so the opening --- is line 1
+    if toml {
+        let table = text.parse::<toml::Table>().map_err(|e| {
+            let line = e.span().map_or(0, |span| text[..span.start].matches('\n').count());
+            PartialError::invalid_front_matter(line + 2, e.message())
+        })?;
+        return Ok(table.into_iter().map(|(key, value)| (IndexableData::String(key), from_toml(value))).collect());
+    }
+    let documents = yaml_rust2::YamlLoader::load_from_str(text).map_err(|e| PartialError::invalid_front_matter(e.marker().line() + 1, e.info()))?;
+    let Some(value) = documents.into_iter().next() else {
+        return Ok(BTreeMap::new()); // nothing but whitespace and comments
+    };
+    match from_yaml(value)? {
+        Data::Nil => Ok(BTreeMap::new()),
+        Data::Table(table) => Ok(table),
+        other => Err(PartialError::invalid_front_matter(2, &format!("expected keys and values, got a {}", other.typename())))
+    }
+}
+
+
+fn from_yaml(value : yaml_rust2::Yaml) -> SitixPartialResult<Data> { // aliases have already been swapped for what they point to
+    use yaml_rust2::Yaml;
+    Ok(match value {
+        Yaml::Null | Yaml::BadValue => Data::Nil,
+        Yaml::Boolean(b) => Data::Boolean(b),
+        Yaml::Integer(n) => Data::Number(n as f64),
+        Yaml::Real(ref n) => Data::Number(value.as_f64().unwrap_or_else(|| n.parse().unwrap_or(f64::NAN))),
+        Yaml::String(s) => Data::String(s),
+        Yaml::Array(items) => Data::table_from_vec(items.into_iter().map(from_yaml).collect::<SitixPartialResult<_>>()?),
+        Yaml::Hash(mapping) => {
+            let mut table = BTreeMap::new();
+            for (key, value) in mapping {
+                table.insert(from_yaml(key)?.into_index()?, from_yaml(value)?);
+            }
+            Data::Table(table)
+        },
+        Yaml::Alias(_) => Data::Nil
+    })
+}
+
+
+fn from_toml(value : toml::Value) -> Data {
+    use toml::Value;
+    match value {
+        Value::String(s) => Data::String(s),
+        Value::Integer(n) => Data::Number(n as f64),
+        Value::Float(n) => Data::Number(n),
+        Value::Boolean(b) => Data::Boolean(b),
+        Value::Datetime(date) => Data::String(date.to_string()),
+        Value::Array(items) => Data::table_from_vec(items.into_iter().map(from_toml).collect()),
+        Value::Table(table) => Data::Table(table.into_iter().map(|(key, value)| (IndexableData::String(key), from_toml(value))).collect())
+    }
+}
@@ -0,0 +1,154 @@
+// ignore rules: which files in the source directory aren't part of the site.
+// rules come from a built-in default set, the `ignore` setting in sitix.stx, and a .sitixignore file at the root of the project,
+// in that order. the syntax is a subset of gitignore:
+//   # comment
+//   *.psd          a pattern without a slash matches a file or directory with that name anywhere in the project
+//   drafts/        a trailing slash only matches directories
+//   /notes.txt     a pattern with a slash in it is matched against the whole project-relative path
+//   posts/**/*.bak ** matches any number of directories
+//   !.well-known   a leading ! un-ignores something an earlier rule ignored
+// `*` and `?` don't match slashes; `[abc]`, `[a-z]` and `[!abc]` work like they do in the shell.
+// the last rule that matches a path decides whether it's ignored. ignoring a directory ignores everything inside it.
+
+
+use std::path::Path;
+
+
+pub const IGNORE_FILE : &str = ".sitixignore";
+const DEFAULT_RULES : &[&str] = &[
+    ".*", // dotfiles; this covers .git, .sitixignore, and vim/emacs lock files like .#foo
+    "*~",
+    "*.swp",
+    "*.swo",
+    "\\#*#", // a leading # would make this a comment
+    "node_modules/"
+];
+
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern : Vec<char>,
+    negated : bool,
+    dir_only : bool,
+    anchored : bool // match against the full relative path, rather than just the filename
+}
+
+
+impl Rule {
+    fn parse(line : &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line)
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line)
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        Some(Rule {
+            pattern : line.chars().collect(),
+            negated,
+            dir_only,
+            anchored
+        })
+    }
+
+    fn matches(&self, path : &[char], is_dir : bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            glob(&self.pattern, path)
+        }
+        else {
+            let name_start = path.iter().rposition(|c| *c == '/').map_or(0, |slash| slash + 1);
+            glob(&self.pattern, &path[name_start..])
+        }
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    rules : Vec<Rule>
+}
+
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        let mut rules = Self { rules : vec![] };
+        rules.add_patterns(DEFAULT_RULES.iter().copied());
+        rules
+    }
+}
+
+
+impl IgnoreRules {
+    pub fn add_patterns<'a>(&mut self, patterns : impl Iterator<Item = &'a str>) {
+        self.rules.extend(patterns.filter_map(Rule::parse));
+    }
+
+    pub fn is_ignored(&self, relative_path : &Path, is_dir : bool) -> bool { // relative_path is relative to the project root
+        let path : Vec<char> = relative_path.to_string_lossy().chars().collect();
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.negated == ignored && rule.matches(&path, is_dir) { // only rules that would flip the verdict are worth checking
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+
+fn glob(pattern : &[char], text : &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => { // zero or more whole directories
+            (0..=text.len()).any(|i| (i == 0 || text[i - 1] == '/') && glob(rest, &text[i..]))
+        },
+        ['*', '*', rest @ ..] => {
+            (0..=text.len()).any(|i| glob(rest, &text[i..]))
+        },
+        ['*', rest @ ..] => {
+            let segment_end = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=segment_end).any(|i| glob(rest, &text[i..]))
+        },
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(c) if *c != '/') && glob(rest, &text[1..])
+        },
+        ['[', class @ ..] => {
+            let Some(close) = class.iter().skip(1).position(|c| *c == ']').map(|close| close + 1) else {
+                return text.first() == Some(&'[') && glob(class, &text[1..]); // no closing bracket, so it's just a bracket
+            };
+            let Some(c) = text.first() else {
+                return false;
+            };
+            let (negated, set) = match class[0] {
+                '!' | '^' => (true, &class[1..close]),
+                _ => (false, &class[..close])
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    found |= set[i] <= *c && *c <= set[i + 2];
+                    i += 3;
+                }
+                else {
+                    found |= set[i] == *c;
+                    i += 1;
+                }
+            }
+            found != negated && *c != '/' && glob(&class[close + 1..], &text[1..])
+        },
+        ['\\', c, rest @ ..] | [c, rest @ ..] => {
+            text.first() == Some(c) && glob(rest, &text[1..])
+        }
+    }
+}
@@ -31,7 +31,8 @@ enum BlockTermMode { // internal data structure describing how a block was termi
 pub struct SitixTree { // contains a program and one or more bodies which are interpreted in various ways by the parser.
     // for instance, the first body (which always matches BlockMode::Main) might be interpreted directly as an Expression if one is needed
     pub content : TokenReader, // main body
-    pub children : Vec<(BlockMode, Vec<TreeChild>)> // this will be parsed as an expression if necessary
+    pub children : Vec<(BlockMode, Vec<TreeChild>)>, // this will be parsed as an expression if necessary
+    pub errors : Vec<Error> // errors the parser recovered from. they still fail the file, but they don't stop the parse
 }
 
 
@@ -39,7 +40,8 @@ impl SitixTree {
     pub fn root(tokens : &mut TokenReader) -> SitixResult<SitixTree> {
         Ok(SitixTree {
             content : TokenReader::new(Vec::new()),
-            children : vec![(BlockMode::Main, Self::parse_contained(tokens)?.1)]
+            children : vec![(BlockMode::Main, Self::parse_contained(tokens)?.1)],
+            errors : vec![]
         })
     }
 
@@ -101,7 +103,8 @@ impl SitixTree {
                 }
                 ret.push(TreeChild::Tree(SitixTree {
                     content : TokenReader::new(block_contents),
-                    children : block_children
+                    children : block_children,
+                    errors : vec![]
                 }));
             }
         }
@@ -5,8 +5,8 @@
 
 
 use crate::ast::*;
-use std::collections::{ HashMap, BTreeMap };
-use std::sync::Arc;
+use std::collections::{ HashMap, HashSet, BTreeMap };
+use std::sync::{ Arc, Mutex, Weak };
 use crate::ffi::*;
 use crate::error::*;
 use crate::utility::Span;
@@ -16,7 +16,8 @@ use crate::filesystem::SitixProject;
 #[derive(Clone)]
 pub enum SitixFunction {
     Builtin(&'static (dyn Fn(&mut InterpreterState, usize, &SitixProject, &[Data]) -> SitixPartialResult<Data> + Send + Sync)),
-    UserDefined(Vec<(usize, Span)>, Box<Expression>)
+    UserDefined(Vec<(usize, Span)>, Box<Expression>, Arc<Environment>) // the environment is captured when the function expression is evaluated,
+                                                                       // so every closure keeps its own bindings
 }
 
 
@@ -34,7 +35,65 @@ impl std::cmp::PartialEq<Self> for SitixFunction {
 }
 
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
+// a single level of variable storage.
+// the resolver guarantees every variable has a unique id, so an environment doesn't need to care about names or shadowing;
+// it just needs to know which bindings belong to *this* invocation of a function, and where to look if it can't find one.
+// environments are reference counted because closures hold on to them long after the call that created them is over.
+// a closure stored in the environment it captured (any global or recursive function) makes a cycle that reference counting can't
+// free, so the interpreter that created an environment empties it when the interpreter is dropped; see InterpreterState::drop.
+#[derive(Debug, Default)]
+pub struct Environment {
+    variables : Mutex<HashMap<usize, Data>>,
+    parent : Option<Arc<Environment>>
+}
+
+
+impl Environment {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn child(parent : &Arc<Environment>) -> Arc<Self> {
+        Arc::new(Self {
+            variables : Mutex::new(HashMap::new()),
+            parent : Some(parent.clone())
+        })
+    }
+
+    pub fn get(&self, index : usize) -> Option<Data> {
+        if let Some(var) = self.variables.lock().unwrap().get(&index) {
+            return Some(var.clone());
+        }
+        self.parent.as_ref()?.get(index)
+    }
+
+    pub fn contains(&self, index : usize) -> bool {
+        self.variables.lock().unwrap().contains_key(&index) || self.parent.as_ref().is_some_and(|parent| parent.contains(index))
+    }
+
+    pub fn create(&self, index : usize, data : Data) {
+        self.variables.lock().unwrap().insert(index, data);
+    }
+
+    fn clear(&self) {
+        let variables = std::mem::take(&mut *self.variables.lock().unwrap());
+        drop(variables); // after unlocking: dropping a closure can drop the last handle to another environment
+    }
+
+    pub fn set(&self, index : usize, data : Data) -> bool { // update the innermost binding for a variable. returns false if there is no such binding.
+        if let Some(var) = self.variables.lock().unwrap().get_mut(&index) {
+            *var = data;
+            return true;
+        }
+        match &self.parent {
+            Some(parent) => parent.set(index, data),
+            None => false
+        }
+    }
+}
+
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone)]
 pub enum IndexableData {
     String(String),
     Number(u64)
@@ -67,10 +126,11 @@ pub enum Data { // data is the *interpreter's* idea of Sitix data.
     Number(f64),
     String(String),
     VariableHandle(usize),
-    Sitix(String, HashMap<String, usize>), // this is a fairly magical high-level builtin type. it is the result of evaluating
-                                          // a SitixExpression.
+    Sitix(String, HashMap<String, Data>), // this is a fairly magical high-level builtin type. it is the result of evaluating
+                                         // a SitixExpression; the map holds the values it exported.
     Table(BTreeMap<IndexableData, Data>),
-    Function(SitixFunction)
+    Function(SitixFunction),
+    Bytes(Vec<u8>) // the contents of a data file that isn't valid utf-8, like an image or a font
 }
 
 impl ToString for Data {
@@ -83,7 +143,8 @@ impl ToString for Data {
             Self::Sitix(s, _) => s.clone(),
             Self::VariableHandle(u) => format!("variable handle {}", u),
             Self::Table(t) => format!("{:?}", t),
-            Self::Function(_) => format!("<function>")
+            Self::Function(_) => format!("<function>"),
+            Self::Bytes(b) => String::from_utf8_lossy(b).to_string()
         }
     }
 }
@@ -135,7 +196,8 @@ impl Data {
             Self::Sitix(_, _) => "text",
             Self::VariableHandle(_) => "reference",
             Self::Table(_) => "table",
-            Self::Function(_) => "function"
+            Self::Function(_) => "function",
+            Self::Bytes(_) => "bytes"
         }.to_string()
     }
 
@@ -160,8 +222,8 @@ impl Data {
                 }
             },
             Self::Sitix(_, t) => {
-                if let Some(index) = t.get(&thing.to_string()) {
-                    Ok(Data::VariableHandle(*index))
+                if let Some(d) = t.get(&thing.to_string()) {
+                    Ok(d.clone())
                 }
                 else {
                     Err(PartialError::invalid_index(thing.to_string()))
@@ -171,6 +233,31 @@ impl Data {
         }
     }
 
+    pub fn set_index(&mut self, thing : IndexableData, value : Data) -> SitixPartialResult<()> {
+        match self {
+            Self::Table(t) => {
+                t.insert(thing, value);
+                Ok(())
+            },
+            Self::Sitix(_, t) => {
+                t.insert(thing.to_string(), value);
+                Ok(())
+            },
+            _ => Err(PartialError::invalid_type("table", self.typename()))
+        }
+    }
+
+    pub fn append(&mut self, value : Data) -> SitixPartialResult<()> { // the new index is one past the highest integer index already in the table
+        match self {
+            Self::Table(t) => {
+                let next = t.keys().filter_map(|key| if let IndexableData::Number(n) = key { Some(n + 1) } else { None }).max().unwrap_or(0);
+                t.insert(IndexableData::Number(next), value);
+                Ok(())
+            },
+            _ => Err(PartialError::invalid_type("table", self.typename()))
+        }
+    }
+
     pub fn table_from_vec(vec : Vec<Data>) -> Data {
         let mut tree = BTreeMap::new();
         for (index, data) in vec.into_iter().enumerate() {
@@ -179,32 +266,80 @@ impl Data {
         Data::Table(tree)
     }
 
+    pub fn add(self, other : Data) -> SitixPartialResult<Data> { // numbers add, anything involving a string concatenates
+        Ok(if let Data::String(s) = self {
+            Data::String(s + &other.to_string())
+        }
+        else if let Data::String(s) = other {
+            Data::String(self.to_string() + &s)
+        }
+        else if let Data::Sitix(s, _) = self {
+            Data::String(s + &other.to_string())
+        }
+        else if let Data::Sitix(s, _) = other {
+            Data::String(self.to_string() + &s)
+        }
+        else {
+            Data::Number(self.force_number()? + other.force_number()?)
+        })
+    }
+
     pub fn call_fun(&self, i : &mut InterpreterState, args : &[Data], node : usize, project : &SitixProject) -> SitixPartialResult<Data> {
         match self.force_function()? {
             SitixFunction::Builtin(built_in) => {
                 built_in(i, node, project, &args)
             },
-            SitixFunction::UserDefined(req_args, contents) => {
+            SitixFunction::UserDefined(req_args, contents, closure) => {
                 if args.len() != req_args.len() {
                     return Err(PartialError::invalid_argument_count());
                 }
-                for ((id, _), content) in req_args.into_iter().zip(args.into_iter()) {
-                    let content = i.deref(content.clone())?;
-                    i.create(id, content);
+                let scope = i.environment(Some(&closure));
+                for ((id, _), content) in req_args.iter().zip(args.iter()) {
+                    scope.create(*id, i.deref(content.clone())?); // arguments are dereferenced in the *caller's* scope
                 }
-                let ret = contents.interpret(i, node, project);
-                ret.map_err(|e| e.discard_context())
+                i.push_frame(scope)?;
+                let ret = contents.interpret(i, node, project).map_err(|e| e.discard_context()).and_then(|ret| i.deref(ret));
+                i.pop_frame();
+                ret
             }
         }
     }
 }
 
 
+pub const DEFAULT_MAX_CALL_DEPTH : usize = 256;
+// a single sitix call goes through a *lot* of rust frames, and how many depends on the template, the build, and the platform; there's no
+// honest way to size a stack for max_call_depth ahead of time. so every expression checks how much native stack is left, and if it's
+// getting low, carries on in a fresh segment on the heap. max_call_depth is what actually bounds recursion.
+const STACK_RED_ZONE : usize = 256 * 1024; // a debug build can burn through most of this between two expressions
+const STACK_SEGMENT : usize = 4 * 1024 * 1024;
+
+
+#[derive(Debug)]
+pub struct Frame { // one invocation of a user-defined function, or the top level of a render
+    scopes : Vec<Arc<Environment>>, // innermost last. the first holds the arguments, and is a child of the function's captured environment;
+                                    // every braced block, loop iteration, and sitix expression inside the call adds another
+    export_scope : usize // which of `scopes` belongs to the innermost sitix expression. exports and params go there, so the rest
+                         // of the expression can see them
+}
+
+
 #[derive(Debug)]
 pub struct InterpreterState {
-    variables : HashMap<usize, Data>,
+    globals : Arc<Environment>,
+    call_stack : Vec<Frame>,
+    pub max_call_depth : usize,
     ffi : Arc<ForeignFunctionInterface>,
-    pub export_table : HashMap<String, usize>
+    pub export_table : HashMap<String, usize>, // exports made by the sitix expression currently being evaluated
+    pub include_params : HashMap<String, Data>, // the parameters passed to the include() currently being evaluated
+    pub side_effects : bool, // set by builtins like set_page_data. an include that sets this doesn't get cached
+    pub include_stack : Vec<(usize, HashMap<String, Data>)>, // the nodes currently being turned into data and the params they got,
+                                                            // outermost first. used to catch include cycles
+    pub emitted : Vec<(String, Data)>, // extra pages written by emit(), keyed by path relative to the output directory
+    pub dependencies : HashSet<usize>, // every node that has been turned into data by this interpreter. the dev server uses this to
+                                       // figure out which pages need to reload when a file changes
+    environments : Vec<Weak<Environment>>, // every environment made by this interpreter, to be emptied when it's dropped
+    environments_pruned : usize // how many were still alive the last time dead ones were thrown out of `environments`
 }
 
 
@@ -218,21 +353,75 @@ impl InterpreterState {
                                                               // note that we don't store the resolver: it's polluted, we don't
                                                               // want it. we just want data about the current variable
                                                               // index mapping.
+        let globals = Environment::new();
         Self {
-            variables : HashMap::new(),
+            environments : vec![Arc::downgrade(&globals)],
+            environments_pruned : 1,
+            call_stack : vec![Frame { scopes : vec![globals.clone()], export_scope : 0 }],
+            globals,
+            max_call_depth : DEFAULT_MAX_CALL_DEPTH,
             ffi,
-            export_table : HashMap::new()
+            export_table : HashMap::new(),
+            include_params : HashMap::new(),
+            side_effects : false,
+            include_stack : vec![],
+            emitted : vec![],
+            dependencies : HashSet::new()
         }
     }
 
-    pub fn new_with_standard_ffi() -> Self {
-        let mut ffi = ForeignFunctionInterface::new();
-        ffi.add_standard_api();
-        Self::new(Arc::new(ffi))
+    pub fn with_max_call_depth(mut self, max_call_depth : usize) -> Self {
+        self.max_call_depth = max_call_depth;
+        self
+    }
+
+    fn environment(&mut self, parent : Option<&Arc<Environment>>) -> Arc<Environment> { // all environments are made here, so drop can find them
+        let environment = match parent {
+            Some(parent) => Environment::child(parent),
+            None => Environment::new()
+        };
+        if self.environments.len() >= self.environments_pruned * 2 { // a long loop of calls makes a lot of short-lived environments
+            self.environments.retain(|environment| environment.strong_count() > 0);
+            self.environments_pruned = self.environments.len().max(1);
+        }
+        self.environments.push(Arc::downgrade(&environment));
+        environment
+    }
+
+    fn scope(&self) -> &Arc<Environment> { // the innermost environment; this is where `let` bindings go
+        self.call_stack.last().unwrap().scopes.last().unwrap()
+    }
+
+    pub fn push_frame(&mut self, scope : Arc<Environment>) -> SitixPartialResult<()> {
+        if self.call_stack.len() > self.max_call_depth { // the bottom frame is the top level, not a call
+            return Err(PartialError::call_depth_exceeded(self.max_call_depth));
+        }
+        self.call_stack.push(Frame { scopes : vec![scope], export_scope : 0 });
+        Ok(())
+    }
+
+    fn push_scope(&mut self) { // open a child of the innermost environment, so closures made in it keep their own copy of its bindings
+        let scope = self.environment(Some(&self.scope().clone()));
+        self.call_stack.last_mut().unwrap().scopes.push(scope);
+    }
+
+    fn pop_scope(&mut self) {
+        self.call_stack.last_mut().unwrap().scopes.pop();
+    }
+
+    fn scoped<T>(&mut self, f : impl FnOnce(&mut Self) -> T) -> T { // run f in a fresh scope, which goes away afterwards even if f fails
+        self.push_scope();
+        let ret = f(self);
+        self.pop_scope();
+        ret
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.call_stack.pop();
     }
 
     pub fn get(&self, index : usize) -> SitixPartialResult<Data> {
-        if let Some(_) = self.variables.get(&index) {
+        if self.scope().contains(index) {
             return Ok(Data::VariableHandle(index));
         }
         else if let Some(_) = self.ffi.get(index) {
@@ -242,16 +431,29 @@ impl InterpreterState {
     }
 
     pub fn create(&mut self, ident : usize, data : Data) -> Data {
-        self.variables.insert(ident, data);
+        self.scope().create(ident, data);
+        Data::VariableHandle(ident)
+    }
+
+    pub fn create_global(&mut self, ident : usize, data : Data) -> Data {
+        self.globals.create(ident, data);
+        Data::VariableHandle(ident)
+    }
+
+    fn create_export(&mut self, ident : usize, data : Data) -> Data {
+        let frame = self.call_stack.last().unwrap();
+        frame.scopes[frame.export_scope].create(ident, data);
         Data::VariableHandle(ident)
     }
 
     pub fn set(&mut self, handle : Data, data : Data) -> SitixPartialResult<()> {
         if let Data::VariableHandle(u) = handle {
-            if let Some(var) = self.variables.get_mut(&u) {
-                *var = data;
+            if self.scope().set(u, data) {
                 Ok(())
             }
+            else if self.ffi.get(u).is_some() {
+                Err(PartialError::read_only())
+            }
             else {
                 Err(PartialError::undefined_symbol())
             }
@@ -261,10 +463,17 @@ impl InterpreterState {
         }
     }
 
+    pub fn collect_exports(&self, exports : HashMap<String, usize>) -> HashMap<String, Data> { // turn an export table into a property map
+        exports.into_iter().filter_map(|(name, index)| {
+            // an export made inside a function call that has since returned is out of reach; it just doesn't become a property
+            Some((name, self.deref(Data::VariableHandle(index)).ok()?))
+        }).collect()
+    }
+
     pub fn deref(&self, data : Data) -> SitixPartialResult<Data> {
         match data {
             Data::VariableHandle(index) => {
-                if let Some(var) = self.variables.get(&index).cloned() {
+                if let Some(var) = self.scope().get(index) {
                     Ok(var)
                 }
                 else if let Some(var) = self.ffi.get(index) {
@@ -280,11 +489,29 @@ impl InterpreterState {
 }
 
 
+impl Drop for InterpreterState {
+    fn drop(&mut self) { // break the closure cycles (see Environment). a closure that outlives its interpreter, say in page data, loses
+                         // the variables it captured along with it
+        for environment in &self.environments {
+            if let Some(environment) = environment.upgrade() {
+                environment.clear();
+            }
+        }
+    }
+}
+
+
 impl SitixExpression {
     pub fn interpret(&self, interpreter : &mut InterpreterState, node : usize, project : &SitixProject) -> SitixResult<Data> {
         Ok(match self {
             Self::Block(b) => {
-                Data::Sitix(b.interpret(interpreter, node, project)?.to_string(), interpreter.export_table.clone())
+                let ret = b.interpret(interpreter, node, project)?;
+                if let Data::Sitix(_, _) = ret { // the root of a file has a sitix expression tail, which carries the file's exports
+                    ret
+                }
+                else {
+                    Data::Sitix(ret.to_string(), HashMap::new())
+                }
             },
             Self::Text(text, _) => Data::Sitix(text.clone(), HashMap::new())
         })
@@ -322,12 +549,28 @@ impl Statement {
     fn interpret(&self, i : &mut InterpreterState, node : usize, project : &SitixProject) -> SitixResult<Data> {
         match self {
             Self::Expression(expr) => expr.interpret(i, node, project),
-            Self::Assign(_, ident, expr, export_name) => {
+            Self::Assign(_, ident, expr, Binding::Param(name)) if i.include_params.contains_key(name) => {
+                let value = i.include_params[name].clone(); // the default isn't evaluated at all if a value was passed in
+                i.create_export(*ident, value);
+                Ok(Data::Nil)
+            },
+            Self::Assign(_, ident, expr, binding) => {
                 let value = expr.interpret(i, node, project)?;
                 let value = i.deref(value).map_err(|e| e.weld(expr.blame()))?;
-                i.create(*ident, value);
-                if let Some(name) = export_name {
-                    i.export_table.insert(name.clone(), *ident);
+                match binding {
+                    Binding::Local => {
+                        i.create(*ident, value);
+                    },
+                    Binding::Param(_) => {
+                        i.create_export(*ident, value);
+                    },
+                    Binding::Global => {
+                        i.create_global(*ident, value);
+                    },
+                    Binding::Export(name) => {
+                        i.create_export(*ident, value);
+                        i.export_table.insert(name.clone(), *ident);
+                    }
                 }
                 Ok(Data::Nil)
             },
@@ -344,6 +587,8 @@ impl Statement {
             Self::Expression(expr) => expr.blame(),
             Self::UnboundLetAssign(_, _, _) => panic!("unreachable"),
             Self::UnboundGlobalAssign(_, _, _) => panic!("unreachable"),
+            Self::UnboundExportAssign(_, _, _) => panic!("unreachable"),
+            Self::UnboundParamAssign(_, _, _) => panic!("unreachable"),
             Self::Assign(span, _, expr, _) => {
                 span.clone().merge(expr.blame())
             },
@@ -356,32 +601,47 @@ impl Statement {
 
 impl Expression {
     fn interpret(&self, i : &mut InterpreterState, node : usize, project : &SitixProject) -> SitixResult<Data> {
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || self.interpret_inner(i, node, project))
+    }
+
+    fn interpret_inner(&self, i : &mut InterpreterState, node : usize, project : &SitixProject) -> SitixResult<Data> {
         match self {
             Self::Literal(_, l) => l.interpret(i, node, project),
             Self::Unary(u) => u.interpret(i, node, project),
             Self::Binary(b) => b.interpret(i, node, project),
             Self::Grouping(e) => e.interpret(i, node, project),
-            Self::Braced(b) => b.interpret(i, node, project),
-            Self::SitixExpression(v) => {
-                let mut result = String::new();
-                for expr in v {
-                    let r = expr.interpret(i, node, project)?;
-                    result += &i.deref(r).map_err(|e| e.weld(expr.blame()))?.to_string();
-                }
-                Ok(Data::Sitix(result, HashMap::new()))
-            },
+            Self::Braced(b) => i.scoped(|i| b.interpret(i, node, project)),
+            Self::SitixExpression(v) => i.scoped(|i| {
+                let outer_exports = std::mem::take(&mut i.export_table);
+                let frame = i.call_stack.last_mut().unwrap();
+                let outer_export_scope = std::mem::replace(&mut frame.export_scope, frame.scopes.len() - 1);
+                let result = Self::concatenate(v, i, node, project);
+                i.call_stack.last_mut().unwrap().export_scope = outer_export_scope;
+                let exports = std::mem::replace(&mut i.export_table, outer_exports);
+                Ok(Data::Sitix(result?, i.collect_exports(exports))) // while the expression's scope is still open, so its exports can be read
+            }),
             Self::True(_) => Ok(Data::Boolean(true)),
             Self::False(_) => Ok(Data::Boolean(false)),
             Self::Nil(_) => Ok(Data::Nil),
             Self::VariableAccess(span, name) => {
                 i.get(*name).map_err(|e| e.weld(span.clone()))
             },
-            Self::Assignment(variable, value) => {
-                let var = variable.interpret(i, node, project)?;
+            Self::Assignment(variable, value) => { // left to right: the target's indices are evaluated (once each) before the value
+                let place = variable.place(i, node, project)?;
                 let val = value.interpret(i, node, project)?;
-                i.set(var, i.deref(val.clone()).map_err(|e| e.weld(value.blame()))?).map_err(|e| e.weld(variable.blame()))?;
+                let val = i.deref(val).map_err(|e| e.weld(value.blame()))?;
+                place.write(i, val.clone())?;
                 Ok(val)
             },
+            Self::CompoundAssignment(variable, op, value) => {
+                let place = variable.place(i, node, project)?;
+                let old = place.read(i)?;
+                let operand = value.interpret(i, node, project)?;
+                let operand = i.deref(operand).map_err(|e| e.weld(value.blame()))?;
+                let new = op.apply(old, operand).map_err(|e| e.weld(self.blame()))?;
+                place.write(i, new.clone())?;
+                Ok(new)
+            },
             Self::IfBranch(_, condition, truthy, falsey) => {
                 let way = condition.interpret(i, node, project)?;
                 let way = i.deref(way).map_err(|e| e.weld(condition.blame()))?.force_boolean().map_err(|e| e.weld(condition.blame()))?;
@@ -403,8 +663,8 @@ impl Expression {
                     let expr = i.deref(expr).map_err(|e| e.weld(entry.content.blame()))?;
                     if let Some(label) = &entry.label {
                         let lbl = label.interpret(i, node, project)?;
-                        let lbl = i.deref(lbl).map_err(|e| e.weld(entry.content.blame()))?;
-                        data.insert(lbl.clone().into_index().map_err(|e| e.weld(entry.content.blame()))?, expr);
+                        let lbl = i.deref(lbl).map_err(|e| e.weld(label.blame()))?;
+                        data.insert(lbl.into_index().map_err(|e| e.weld(label.blame()))?, expr);
                     }
                     else {
                         data.insert(IndexableData::Number(current_index), expr);
@@ -419,8 +679,10 @@ impl Expression {
                     let do_exec = cond.interpret(i, node, project)?;
                     let do_exec = i.deref(do_exec).map_err(|e| e.weld(cond.blame()))?;
                     if do_exec.force_boolean().map_err(|e| e.weld(cond.blame()))? {
-                        let expressive_output = body.interpret(i, node, project)?;
-                        let expressive_output = i.deref(expressive_output).map_err(|e| e.weld(body.blame()))?;
+                        let expressive_output = i.scoped(|i| { // a new scope every time around
+                            let output = body.interpret(i, node, project)?;
+                            i.deref(output).map_err(|e| e.weld(body.blame()))
+                        })?;
                         out += &expressive_output.to_string();
                     }
                     else {
@@ -434,12 +696,13 @@ impl Expression {
                 let fun = i.deref(fun).map_err(|e| e.weld(func.blame()))?;
                 let mut to_args = vec![];
                 for arg in args {
-                    to_args.push(arg.interpret(i, node, project)?);
+                    let data = arg.interpret(i, node, project)?;
+                    to_args.push(i.deref(data).map_err(|e| e.weld(arg.blame()))?);
                 }
                 fun.call_fun(i, &to_args, node, project).map_err(|e| e.weld(func.blame()))
             },
             Self::Function(_, args, contents) => {
-                Ok(Data::Function(SitixFunction::UserDefined(args.clone(), contents.clone())))
+                Ok(Data::Function(SitixFunction::UserDefined(args.clone(), contents.clone(), i.scope().clone())))
             },
             Self::Each(span, cond, var, second_var, body) => {
                 let mut out = String::new();
@@ -447,12 +710,14 @@ impl Expression {
                 let array = i.deref(array).map_err(|e| e.weld(span.clone()))?;
                 let map = array.force_table().map_err(|e| e.weld(span.clone()))?;
                 for (index, item) in &map {
-                    i.create(*var, item.clone());
-                    if let Some(v) = second_var {
-                        i.create(*v, index.clone().into_data());
-                    }
-                    let expr_out = body.interpret(i, node, project)?;
-                    let expr_out = i.deref(expr_out).map_err(|e| e.weld(body.blame()))?;
+                    let expr_out = i.scoped(|i| { // each iteration gets its own bindings, so closures made in the body don't all see the last item
+                        i.create(*var, item.clone());
+                        if let Some(v) = second_var {
+                            i.create(*v, index.clone().into_data());
+                        }
+                        let output = body.interpret(i, node, project)?;
+                        i.deref(output).map_err(|e| e.weld(body.blame())) // before the iteration's variables go away
+                    })?;
                     out += &expr_out.to_string();
                 }
                 Ok(Data::String(out))
@@ -461,11 +726,64 @@ impl Expression {
                 let expr = _expr.interpret(i, node, project)?;
                 let expr = i.deref(expr).map_err(|e| e.weld(_expr.blame()))?;
                 Ok(expr.index(IndexableData::String(id.clone())).map_err(|e| e.weld(_expr.blame()))?)
+            },
+            Self::Index(span, expr, index) => {
+                let container = expr.interpret(i, node, project)?;
+                let container = i.deref(container).map_err(|e| e.weld(expr.blame()))?;
+                if let Some(index) = index {
+                    let key = index.interpret(i, node, project)?;
+                    let key = i.deref(key).map_err(|e| e.weld(index.blame()))?.into_index().map_err(|e| e.weld(index.blame()))?;
+                    container.index(key).map_err(|e| e.weld(self.blame()))
+                }
+                else {
+                    Err(PartialError::anonymous_index_read().weld(span.clone()))
+                }
             }
             _ => panic!("unreachable")
         }
     }
 
+    fn concatenate(v : &[SitixExpression], i : &mut InterpreterState, node : usize, project : &SitixProject) -> SitixResult<String> {
+        let mut result = String::new();
+        for expr in v {
+            let r = expr.interpret(i, node, project)?;
+            result += &i.deref(r).map_err(|e| e.weld(expr.blame()))?.to_string();
+        }
+        Ok(result)
+    }
+
+    fn place(&self, i : &mut InterpreterState, node : usize, project : &SitixProject) -> SitixResult<Place> { // work out which place an assignment target names,
+                                                                                                           // evaluating each index exactly once, outside-in
+        match self {
+            Self::VariableAccess(span, id) => Ok(Place { variable : *id, span : span.clone(), keys : vec![] }),
+            Self::Grouping(e) => e.place(i, node, project),
+            Self::Index(span, expr, index) => {
+                let mut place = expr.place(i, node, project)?;
+                if place.keys.last().is_some_and(|(key, _)| key.is_none()) { // t[][0] = x
+                    return Err(PartialError::anonymous_index_read().weld(span.clone()));
+                }
+                let key = match index {
+                    Some(index) => {
+                        let key = index.interpret(i, node, project)?;
+                        Some(i.deref(key).map_err(|e| e.weld(index.blame()))?.into_index().map_err(|e| e.weld(index.blame()))?)
+                    },
+                    None => None
+                };
+                place.keys.push((key, self.blame()));
+                Ok(place)
+            },
+            Self::DotAccess(expr, id) => {
+                let mut place = expr.place(i, node, project)?;
+                if place.keys.last().is_some_and(|(key, _)| key.is_none()) {
+                    return Err(PartialError::anonymous_index_read().weld(self.blame()));
+                }
+                place.keys.push((Some(IndexableData::String(id.clone())), self.blame()));
+                Ok(place)
+            },
+            _ => Err(PartialError::invalid_assignment_target().weld(self.blame()))
+        }
+    }
+
     fn blame(&self) -> Span {
         match self {
             Self::Literal(span, _) => span.clone(),
@@ -485,17 +803,79 @@ impl Expression {
             Self::Nil(span) => span.clone(),
             Self::VariableAccess(span, _) => span.clone(),
             Self::Assignment(variable, value) => variable.blame().merge(value.blame()),
+            Self::CompoundAssignment(variable, _, value) => variable.blame().merge(value.blame()),
             Self::IfBranch(span, _, truthy, _) => span.clone().merge(truthy.blame()),
             Self::Table(span, _) => span.clone(),
             Self::While(span, _, body) => span.clone().merge(body.blame()),
             Self::Call(fun, args) => if let Some(last) = args.last() { fun.blame().merge(last.blame()) } else { fun.blame() },
             Self::Function(span, _, contents) => span.clone().merge(contents.blame()),
             Self::DotAccess(expr, _) => expr.blame(),
+            Self::Index(span, expr, _) => expr.blame().merge(span.clone()),
             _ => panic!("unreachable")
         }
     }
 }
 
+struct Place { // somewhere an assignment can write to: a variable, and the already-evaluated keys leading into it.
+               // None is the anonymous index, t[], which can only come last
+    variable : usize,
+    span : Span,
+    keys : Vec<(Option<IndexableData>, Span)>
+}
+
+
+impl Place {
+    fn read(&self, i : &InterpreterState) -> SitixResult<Data> {
+        let mut data = i.deref(Data::VariableHandle(self.variable)).map_err(|e| e.weld(self.span.clone()))?;
+        for (key, span) in &self.keys {
+            let Some(key) = key else {
+                return Err(PartialError::anonymous_index_read().weld(span.clone()));
+            };
+            data = data.index(key.clone()).and_then(|data| i.deref(data)).map_err(|e| e.weld(span.clone()))?;
+        }
+        Ok(data)
+    }
+
+    fn write(&self, i : &mut InterpreterState, value : Data) -> SitixResult<()> {
+        let root = i.deref(Data::VariableHandle(self.variable)).map_err(|e| e.weld(self.span.clone()))?;
+        let root = Self::write_into(i, root, &self.keys, value)?;
+        i.set(Data::VariableHandle(self.variable), root).map_err(|e| e.weld(self.span.clone()))
+    }
+
+    fn write_into(i : &InterpreterState, container : Data, keys : &[(Option<IndexableData>, Span)], value : Data) -> SitixResult<Data> {
+        // tables are values, so every table along the way is copied, updated, and put back into its parent
+        let Some(((key, span), rest)) = keys.split_first() else {
+            return Ok(value);
+        };
+        let mut container = container;
+        match key {
+            Some(key) => {
+                let value = if rest.is_empty() { value } else {
+                    let inner = container.index(key.clone()).and_then(|inner| i.deref(inner)).map_err(|e| e.weld(span.clone()))?;
+                    Self::write_into(i, inner, rest, value)?
+                };
+                container.set_index(key.clone(), value).map_err(|e| e.weld(span.clone()))?;
+            },
+            None => {
+                container.append(value).map_err(|e| e.weld(span.clone()))?;
+            }
+        }
+        Ok(container)
+    }
+}
+
+
+impl CompoundOp {
+    fn apply(&self, one : Data, two : Data) -> SitixPartialResult<Data> {
+        Ok(match self {
+            Self::Add => one.add(two)?,
+            Self::Sub => Data::Number(one.force_number()? - two.force_number()?),
+            Self::Mul => Data::Number(one.force_number()? * two.force_number()?),
+            Self::Div => Data::Number(one.force_number()? / two.force_number()?)
+        })
+    }
+}
+
 impl Unary {
     fn interpret(&self, i : &mut InterpreterState, node : usize, project : &SitixProject) -> SitixResult<Data> {
         Ok(match self {
@@ -577,23 +957,7 @@ impl Binary {
                 let two = _two.interpret(i, node, project)?;
                 let one = i.deref(one).map_err(|e| e.weld(_one.blame().merge(_two.blame())))?;
                 let two = i.deref(two).map_err(|e| e.weld(_one.blame().merge(_two.blame())))?;
-                if let Data::String(s) = one {
-                    Data::String(s + &two.to_string())
-                }
-                else if let Data::String(s) = two {
-                    Data::String(one.to_string() + &s)
-                }
-                else if let Data::Sitix(s, _) = one {
-                    Data::String(s + &two.to_string())
-                }
-                else if let Data::Sitix(s, _) = two {
-                    Data::String(one.to_string() + &s)
-                }
-                else {
-                    let one = one.force_number().map_err(|e| e.weld(_one.blame().merge(_two.blame())))?;
-                    let two = two.force_number().map_err(|e| e.weld(_one.blame().merge(_two.blame())))?;
-                    Data::Number(one + two)
-                }
+                one.add(two).map_err(|e| e.weld(_one.blame().merge(_two.blame())))?
             },
             Self::Sub(_one, _two) => {
                 let one = _one.interpret(i, node, project)?;
@@ -688,3 +1052,70 @@ impl Binary {
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use crate::ffi::ForeignFunctionInterface;
+    use crate::filesystem::SitixProject;
+    use crate::inflate::SitixTree;
+    use crate::interpret::InterpreterState;
+    use crate::lexer;
+    use crate::parse::TokenReader;
+    use crate::resolve::ResolverState;
+
+    fn render(source : &str) -> String { // the whole pipeline, on a file that isn't in any project
+        let mut ffi = ForeignFunctionInterface::new();
+        ffi.add_standard_api();
+        let ffi = Arc::new(ffi);
+        let tokens = lexer::lexer(lexer::FileReader::new("test.stx".to_string(), source)).unwrap();
+        let mut tree = SitixTree::root(&mut TokenReader::new(tokens)).unwrap();
+        let ast = tree.parse_root("test.stx".to_string()).unwrap().resolve(&mut ResolverState::new(ffi.clone())).unwrap();
+        let project = SitixProject::new(PathBuf::from("/nonexistent"));
+        let mut interpreter = InterpreterState::new(ffi);
+        ast.interpret(&mut interpreter, 0, &project).unwrap().to_string()
+    }
+
+    #[test]
+    fn variable_label() {
+        assert_eq!(render(r#"[!][let index = "hello"; let t = [index : "world"]; t["hello"]]"#), "world");
+    }
+
+    #[test]
+    fn call_label() {
+        assert_eq!(render(r#"[!][let key = @(n) "item-" + n; let t = [key(1) : "one", key(2) : "two"]; t["item-2"]]"#), "two");
+    }
+
+    #[test]
+    fn concatenated_label() {
+        assert_eq!(render(r#"[!][let prefix = "post"; let t = [prefix + "-" + 3 : "third"]; t["post-3"]]"#), "third");
+    }
+
+    #[test]
+    fn labels_leave_positional_entries_alone() {
+        assert_eq!(render(r#"[!][let n = 2; let t = [n * 2 : "four", "plain"]; t[4] + " " + t[0]]"#), "four plain");
+    }
+
+    #[test]
+    fn compound_assignment_index_evaluated_once() {
+        assert_eq!(render(r#"[!][let t = [10, 20, 30]; let i = 0; t[i += 1] += 5; t[0] + "," + t[1] + "," + t[2] + " " + i]"#), "10,25,30 1");
+    }
+
+    #[test]
+    fn assignment_index_evaluated_once() {
+        assert_eq!(render(r#"[!][let b = [[0], [0]]; let i = 0; b[i += 1][0] = 7; b[0][0] + "," + b[1][0] + " " + i]"#), "0,7 1");
+    }
+
+    #[test]
+    fn closures_keep_their_own_bindings() {
+        assert_eq!(render(r#"[!][let make = @(x) @() x; let a = make("a"); let b = make("b"); a() + b()]"#), "ab");
+    }
+
+    #[test]
+    fn loop_iterations_get_their_own_scope() {
+        assert_eq!(render(r#"[!][global fns = []; each ["a", "b", "c"] -> item { fns[] = @() item; "" }; fns[0]() + fns[1]() + fns[2]()]"#), "abc");
+        assert_eq!(render(r#"[!][global gs = []; global i = 0; while i < 3 { let j = i; gs[] = @() j; i += 1; "" }; gs[0]() + "," + gs[1]() + "," + gs[2]()]"#), "0,1,2");
+    }
+}
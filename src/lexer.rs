@@ -1,7 +1,6 @@
 // contains the lexer function
 use crate::utility::*;
 use crate::error::{ SitixResult, Error };
-use std::path::Path;
 
 
 pub struct FileReader {
@@ -13,10 +12,10 @@ pub struct FileReader {
 
 
 impl FileReader {
-    pub fn open(name : impl AsRef<Path>) -> FileReader {
+    pub fn new(name : String, source : &str) -> FileReader { // name is what spans will call this file
         FileReader {
-            file : std::fs::read_to_string(&name).unwrap().chars().collect(),
-            name : name.as_ref().file_name().unwrap().to_str().unwrap().to_string(),
+            file : source.chars().collect(),
+            name,
             span_start : 0,
             current_byte : 0,
         }
@@ -76,8 +75,9 @@ pub fn lexer(mut buffer : FileReader) -> SitixResult<Vec<Token>> { // please for
                 output.push(Token::new(TokenType::BlockOpen, buffer.get_span()));
                 let mut close_level = 1; // count open-brackets and close-brackets. if it reaches 0, we need to back into text buffering mode
                 buffer.skip(char::is_whitespace)?;
-                'inner_expression : while let Ok(c) = buffer.next() {
-                    buffer.open_span();
+                'inner_expression : loop {
+                    buffer.open_span(); // spans are [start, end) in chars, so the span has to open *before* the first character is consumed
+                    let Ok(c) = buffer.next() else { break; };
                     if c.is_alphabetic() || c == '_' { // parse an ident
                         let mut idb = String::new();
                         idb.push(c);
@@ -103,6 +103,8 @@ pub fn lexer(mut buffer : FileReader) -> SitixResult<Vec<Token>> { // please for
                             "not" => TokenType::Not,
                             "let" => TokenType::Let,
                             "global" => TokenType::Global,
+                            "export" => TokenType::Export,
+                            "param" => TokenType::Param,
                             "debugger" => TokenType::Debugger,
                             "fun" => TokenType::Fun,
                             _ => { TokenType::Literal(Literal::Ident(idb)) }
@@ -0,0 +1,115 @@
+// live reload for the development server.
+// every rendered html page gets a little script injected into it. the script knows which nodes went into the page, and long-polls
+// the server; whenever the watcher thread changes a node it bumps the generation counter, which wakes up every poller. if any of
+// the changed nodes are ones the page depends on, the page reloads itself.
+// long-polling is used instead of server-sent events because rouille is happier with short-lived responses.
+
+
+use std::collections::{ HashSet, VecDeque };
+use std::sync::{ Condvar, Mutex };
+use std::time::Duration;
+
+
+pub const POLL_PATH : &str = "/__sitix/poll";
+const POLL_TIMEOUT : Duration = Duration::from_secs(25); // browsers (and proxies) get grumpy about requests that hang for much longer than this
+const LOG_LENGTH : usize = 1024; // how many changes we remember. a page that falls further behind than this just reloads
+
+
+enum Change {
+    Node(usize),
+    Everything // something happened that we can't pin to a node, like a file appearing at the root of the project
+}
+
+
+#[derive(Default)]
+struct ChangeLog {
+    changes : VecDeque<Change>, // the most recent changes, oldest first
+    dropped : usize // how many older changes have been forgotten to make room. the generation is this plus the length of the log
+}
+
+
+impl ChangeLog {
+    fn generation(&self) -> usize {
+        self.dropped + self.changes.len()
+    }
+}
+
+
+#[derive(Default)]
+pub struct LiveReload {
+    log : Mutex<ChangeLog>,
+    wakeup : Condvar
+}
+
+
+impl LiveReload {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn generation(&self) -> usize {
+        self.log.lock().unwrap().generation()
+    }
+
+    pub fn changed(&self, node : Option<usize>) { // None means "reload every page"
+        let mut log = self.log.lock().unwrap();
+        log.changes.push_back(match node {
+            Some(node) => Change::Node(node),
+            None => Change::Everything
+        });
+        if log.changes.len() > LOG_LENGTH {
+            log.changes.pop_front();
+            log.dropped += 1;
+        }
+        self.wakeup.notify_all();
+    }
+
+    pub fn poll(&self, since : usize) -> String { // block until something changes after generation `since` (or we time out),
+                                                   // and return a json object describing what changed
+        let log = self.log.lock().unwrap();
+        let (log, _) = self.wakeup.wait_timeout_while(log, POLL_TIMEOUT, |log| log.generation() == since).unwrap();
+        let mut nodes = HashSet::new();
+        // either the server restarted since the page was loaded, or the changes it missed have been forgotten. either way, we can't know what changed
+        let mut everything = since > log.generation() || since < log.dropped;
+        for change in log.changes.iter().skip(since.saturating_sub(log.dropped)) {
+            match change {
+                Change::Node(node) => { nodes.insert(*node); },
+                Change::Everything => { everything = true; }
+            }
+        }
+        format!("{{\"generation\":{},\"everything\":{},\"nodes\":[{}]}}", log.generation(), everything,
+            nodes.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(","))
+    }
+
+    pub fn inject(&self, html : String, dependencies : &HashSet<usize>) -> String { // add the client script to an html page
+        let script = format!(r#"<script>
+(function() {{
+    var dependencies = [{}];
+    var generation = {};
+    function poll() {{
+        fetch("{}?since=" + generation).then(function(response) {{ return response.json(); }}).then(function(message) {{
+            generation = message.generation;
+            if (message.everything || message.nodes.some(function(node) {{ return dependencies.indexOf(node) != -1; }})) {{
+                location.reload();
+            }}
+            else {{
+                poll();
+            }}
+        }}, function() {{
+            setTimeout(poll, 1000); // the server is probably restarting
+        }});
+    }}
+    poll();
+}})();
+</script>
+"#, dependencies.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(","), self.generation(), POLL_PATH);
+        if let Some(end) = html.rfind("</body>") {
+            let mut html = html;
+            html.insert_str(end, &script);
+            html
+        }
+        else {
+            html + &script
+        }
+    }
+}
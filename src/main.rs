@@ -10,8 +10,17 @@ mod ffi;
 use ffi::*;
 mod error;
 mod filesystem;
+mod config;
+mod ignore;
+mod mime;
+mod livereload;
+mod markdown;
+mod frontmatter;
 use clap::{ Parser, Subcommand };
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
+use std::hash::{ DefaultHasher, Hash, Hasher };
+use std::time::{ SystemTime, UNIX_EPOCH };
+use std::collections::HashSet;
 use crate::resolve::*;
 use inotify::EventMask;
 
@@ -52,6 +61,8 @@ use inotify::EventMask;
 
 use std::sync::{ Arc, Mutex };
 use crate::filesystem::SitixProject;
+use crate::livereload::LiveReload;
+use crate::utility::escape_html;
 
 
 #[derive(Debug, Subcommand)]
@@ -59,15 +70,35 @@ enum Command {
     Static {
         path : PathBuf, // input directory
 
-        /// Sets the output directory
+        /// Sets the output directory, overriding the project config
         #[arg(short, long, value_name = "FILE")]
-        output : Option<String> // the DIRECTORY to throw templated files in. templated files will have the same name as their original files,
+        output : Option<String>, // the DIRECTORY to throw templated files in. templated files will have the same name as their original files,
                                 // so be smart about this.
                                 // sitix will never overwrite a directory that does not contain a .sitix file; this is to ensure you don't accidentally
                                 // do sitix static -o . and overwite your entire project.
+
+        /// Keep rendering after a file fails, instead of stopping at the first failure
+        #[arg(short, long)]
+        keep_going : bool,
+
+        /// Write into the output directory even if it doesn't contain a .sitix file
+        #[arg(short, long)]
+        force : bool,
+
+        /// Remove files in the output directory that don't correspond to anything in the project
+        #[arg(long)]
+        clean : bool
     },
     Dev {
         path : PathBuf, // input directory
+
+        /// Sets the address the development server listens on, overriding the project config
+        #[arg(long)]
+        host : Option<String>,
+
+        /// Sets the port the development server listens on, overriding the project config
+        #[arg(short, long)]
+        port : Option<u16>
     }
 }
 
@@ -76,54 +107,207 @@ enum Command {
 #[command(version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
-    command : Command
+    command : Command,
+
+    /// Sets the maximum depth of nested function calls
+    #[arg(long, global = true, value_name = "DEPTH", default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+    max_call_depth : usize
 }
 
 
-fn handler(request : &rouille::Request, project : &Arc<Mutex<SitixProject>>) -> rouille::Response {
+fn handler(request : &rouille::Request, project : &Arc<Mutex<SitixProject>>, ffi : &Arc<ForeignFunctionInterface>, live : &LiveReload, max_call_depth : usize) -> rouille::Response {
+    if request.url() == livereload::POLL_PATH {
+        let since = request.get_param("since").and_then(|since| since.parse().ok()).unwrap_or(0);
+        return rouille::Response::from_data("application/json", live.poll(since)).with_no_cache();
+    }
     let project = project.lock().unwrap();
-    let mut interpreter = InterpreterState::new_with_standard_ffi();
-    let node = if let Some(node) = project.search(None, request.url()) { node }
-                else if let Some(node) = project.search(None, request.url() + "index.html") {node}
+    if let Some((e, dependencies)) = project.load_error(&request.url()) {
+        return rouille::Response::html(live.inject(error_page(&project.report_html(e)), &dependencies)).with_status_code(500);
+    }
+    let mut interpreter = InterpreterState::new(ffi.clone()).with_max_call_depth(max_call_depth);
+    let (node, virtual_page) = if let Some(node) = project.search(None, request.url()) { (node, None) }
+                else if let Some(node) = project.search(None, request.url() + "index.html") { (node, None) }
+                else if let Some((node, path)) = find_virtual_page(&project, request.url()) { (node, Some(path)) }
                 else { return rouille::Response::empty_404(); };
-    match project.into_data(node, &mut interpreter) {
-        Ok(data) => {
-            let data = data.to_string();
-            let path = PathBuf::from(request.url());
-            return rouille::Response::from_data(if let Some(ext) = path.extension() {
-                match ext.to_str() {
-                    Some("html") => "text/html",
-                    Some("css") => "text/css",
-                    Some("js") => "application/javascript",
-                    _ => "text/plain"
-                }
-            } else { "text/html" }, data);
+    project.clear_include_cache(); // every render gets a fresh interpreter, so nothing cached for the last one is any good
+    // a panic in here would poison the project lock and take the dev server down with it, so we catch it and show it like any other error.
+    // the interpreter's dependencies are still good afterwards, which is all we need from it
+    let result = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| project.into_data(node, &mut interpreter))) {
+        Ok(Ok(data)) => Ok(project.record_emitted(node, &interpreter.emitted).map(|_| data)),
+        other => other
+    };
+    let result = match (result, virtual_page) { // for an emitted page, what we serve is whatever the node emitted at that path, not the node itself
+        (Ok(Ok(_)), Some(path)) => match interpreter.emitted.iter().rev().find(|(emitted, _)| *emitted == path) {
+            Some((_, data)) => Ok(Ok((data.clone(), path, true))),
+            None => { return rouille::Response::empty_404(); } // the template stopped emitting it
+        },
+        (Ok(Ok(data)), None) if project.is_markdown(node) => { // a preview of the rendered body, served as html
+            Ok(Ok((data.index(IndexableData::String("body".to_string())).unwrap_or(Data::Nil), project.get_name(node).unwrap() + ".html", true)))
+        },
+        (Ok(Ok(data)), None) => Ok(Ok((data, project.get_name(node).unwrap(), project.is_sitix(node)))), // the node's name, not the url, so / gets
+                                                                                                   // index.html's type
+        (Ok(Err(e)), _) => Ok(Err(e)),
+        (Err(panic), _) => Err(panic)
+    };
+    match result {
+        Ok(Ok((data, name, rendered))) => {
+            let mime = mime::mime_type(Path::new(&name), rendered);
+            let data = match data {
+                Data::Bytes(bytes) => bytes,
+                data if mime::is_html(&mime) => live.inject(data.to_string(), &interpreter.dependencies).into_bytes(),
+                data => data.to_string().into_bytes()
+            };
+            let modified = interpreter.dependencies.iter().filter_map(|dep| project.modified(*dep)).max();
+            cached_response(request, mime, data, modified)
+        },
+        Ok(Err(e)) => {
+            println!("{}", project.report(&e));
+            rouille::Response::html(live.inject(error_page(&project.report_html(&e)), &interpreter.dependencies)).with_status_code(500)
         },
-        Err(e) => {
-            return rouille::Response::text(e.to_string());
+        Err(panic) => {
+            let message = if let Some(message) = panic.downcast_ref::<&str>() { message.to_string() }
+                          else if let Some(message) = panic.downcast_ref::<String>() { message.clone() }
+                          else { "the interpreter panicked".to_string() };
+            let body = format!("<h2>Internal Error</h2>\n<p class=\"reason\">{}</p>\n", escape_html(&message));
+            rouille::Response::html(live.inject(error_page(&body), &interpreter.dependencies)).with_status_code(500)
+        }
+    }
+}
+
+
+fn find_virtual_page(project : &SitixProject, url : String) -> Option<(usize, String)> { // look up a page made with emit(), returning the node
+                                                                                       // that emits it and its output-relative path
+    let url = url.trim_start_matches('/').to_string();
+    [url.clone(), url.trim_end_matches('/').to_string() + "/index.html"].into_iter().find_map(|path| Some((project.find_virtual_page(&path)?, path)))
+}
+
+
+fn discover_emitted_pages(project : &SitixProject, ffi : &Arc<ForeignFunctionInterface>, max_call_depth : usize) {
+    // render every template to find out which pages it emits, so the dev server can route them. this happens at startup and after
+    // every batch of changes, and runs the templates' print()s and set_page_data()s once each time, just like a static build would
+    project.clear_virtual_pages();
+    for node in project.renderable() {
+        project.clear_include_cache();
+        let mut interpreter = InterpreterState::new(ffi.clone()).with_max_call_depth(max_call_depth);
+        if let Ok(Ok(_)) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| project.into_data(node, &mut interpreter))) { // a broken template
+                                                                                                                                   // shows its error when
+                                                                                                                                   // it's requested
+            if let Err(e) = project.record_emitted(node, &interpreter.emitted) {
+                println!("{}", project.report(&e));
+            }
         }
     }
 }
 
 
+fn cached_response(request : &rouille::Request, mime : String, body : Vec<u8>, modified : Option<SystemTime>) -> rouille::Response { // attach validators, and
+                                                                                                                                  // answer conditional requests with a 304
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+    let not_modified = if let Some(tags) = request.header("If-None-Match") { // takes precedence over If-Modified-Since when both are sent
+        tags.split(',').map(str::trim).any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+    }
+    else if let (Some(since), Some(modified)) = (request.header("If-Modified-Since").and_then(|since| httpdate::parse_http_date(since).ok()), modified) {
+        let seconds = |time : SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()); // http dates only have second precision
+        seconds(modified) <= seconds(since)
+    }
+    else {
+        false
+    };
+    let mut response = rouille::Response::from_data(mime, body);
+    if not_modified {
+        response.status_code = 304;
+        response.data = rouille::ResponseBody::empty();
+    }
+    let response = response.with_unique_header("ETag", etag).with_unique_header("Cache-Control", "no-cache"); // always revalidate; this is a dev server
+    match modified {
+        Some(modified) => response.with_unique_header("Last-Modified", httpdate::fmt_http_date(modified)),
+        None => response
+    }
+}
+
+
+fn error_page(body : &str) -> String { // the dev server's error overlay. body is html from SitixProject::report_html
+    format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>sitix error</title>
+<style>
+    body {{ background: #1e1e1e; color: #e0e0e0; font-family: sans-serif; margin: 2em; }}
+    h2 {{ color: #ff6b6b; margin-bottom: 0.2em; }}
+    h3 {{ color: #aaaaaa; }}
+    .location {{ color: #aaaaaa; font-family: monospace; margin-top: 0; }}
+    .reason {{ font-size: 1.2em; }}
+    .cause {{ border-left: 3px solid #555555; padding-left: 1em; }}
+    pre {{ background: #2d2d2d; padding: 1em; overflow-x: auto; tab-size: 4; }}
+</style>
+</head>
+<body>
+{}
+</body>
+</html>
+"#, body)
+}
+
+
+fn project_ffi(project : &SitixProject) -> Arc<ForeignFunctionInterface> { // the standard api, plus the project's `site` table
+    let mut ffi = ForeignFunctionInterface::new();
+    ffi.add_standard_api();
+    ffi.add("site".to_string(), project.config.to_data());
+    Arc::new(ffi)
+}
+
+
 fn main() {
     let cli = Cli::parse();
     match cli.command {
-        Command::Static { path, output } => {
-            let out = std::path::absolute(if let Some(output) = output { output } else { "output".to_string() }).unwrap();
+        Command::Static { path, output, keep_going, force, clean } => {
             let metadata = std::fs::metadata(&path).unwrap();
             if metadata.file_type().is_dir() {
-                let mut ffi = ForeignFunctionInterface::new();
-                ffi.add_standard_api();
-                let ffi = Arc::new(ffi);
+                let mut project = filesystem::SitixProject::new(path);
+                if let Some(output) = output {
+                    project.set_output(output);
+                }
+                let out = project.output().to_path_buf();
+                let ffi = project_ffi(&project);
 
                 let mut resolver = ResolverState::new(ffi.clone());
-                let mut project = filesystem::SitixProject::new(path);
-                project.load_dir(None, &mut resolver).unwrap();
+                if let Err(e) = project.load_dir(None, &mut resolver) {
+                    println!("{}", project.report(&e));
+                    std::process::exit(1);
+                }
 
-                let mut interpreter = InterpreterState::new(ffi.clone());
+                if let Err(e) = SitixProject::prepare_output(&out, force) {
+                    println!("{}", e);
+                    std::process::exit(1);
+                }
 
-                project.render(out.into(), &mut interpreter);
+                let mut interpreter = InterpreterState::new(ffi.clone()).with_max_call_depth(cli.max_call_depth);
+                let summary = project.render(out.clone(), &mut interpreter, keep_going);
+                for (path, e) in &summary.failed {
+                    println!("Failed to render {}\n{}", path.display(), project.report(&**e));
+                }
+                println!("{} rendered, {} copied, {} failed", summary.rendered.len(), summary.copied.len(), summary.failed.len());
+                if clean && !summary.succeeded() { // we can't know what a failed template would have written, so cleaning could delete good pages
+                    println!("not cleaning the output directory, since the build failed");
+                }
+                else if clean {
+                    match project.clean(&out, &summary.rendered) {
+                        Ok(removed) => println!("removed {} stale outputs", removed.len()),
+                        Err(e) => {
+                            println!("failed to clean the output directory: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                if !summary.succeeded() {
+                    if !keep_going {
+                        println!("stopped at the first failure; pass --keep-going to render everything else anyway");
+                    }
+                    std::process::exit(1);
+                }
             }
             else if metadata.file_type().is_file() {
                 panic!("at the moment, parsing a single file is not supported.");
@@ -132,59 +316,111 @@ fn main() {
                 panic!("no such file!");
             }
         },
-        Command::Dev { path } => {
+        Command::Dev { path, host, port } => {
             let metadata = std::fs::metadata(&path).unwrap();
             if metadata.file_type().is_dir() {
-                let mut ffi = ForeignFunctionInterface::new();
-                ffi.add_standard_api();
-                let ffi = Arc::new(ffi);
+                let mut project = filesystem::SitixProject::new(path);
+                if let Some(host) = host {
+                    project.config.host = host;
+                }
+                if let Some(port) = port {
+                    project.config.port = port;
+                }
+                let address = format!("{}:{}", project.config.host, project.config.port);
+                let ffi = project_ffi(&project);
 
                 let mut resolver = ResolverState::new(ffi.clone());
-                let mut project = filesystem::SitixProject::new(path.into());
                 if let Err(e) = project.load_dir(None, &mut resolver) {
-                    println!("{}", e);
+                    println!("{}", project.report(&e));
                 }
 
+                discover_emitted_pages(&project, &ffi, cli.max_call_depth);
                 let project = Arc::new(Mutex::new(project));
+                let live = Arc::new(LiveReload::new());
 
                 std::thread::spawn({
                     let project_clone = project.clone();
+                    let live = live.clone();
+                    let ffi = ffi.clone();
                     let mut notify = project_clone.lock().unwrap().setup_inotifier();
                     move || {
                         loop {
                             let mut buffer = [0; 4096];
                             let events = notify.read_events_blocking(&mut buffer).expect("failed reading inotify events");
+                            let mut modified = HashSet::new(); // a single write can produce a pile of events, so reload each file once per batch
                             for event in events {
                                 let mut project = project_clone.lock().unwrap();
                                 let node = project.search_watch_descriptor(&event.wd);
-                                if event.mask.contains(EventMask::DELETE_SELF) {
-                                    if let Some(node) = node {
-                                        project.delete(node);
+                                if event.name.is_some_and(|name| project.is_ignored(node, name.to_str().unwrap())) {
+                                    continue; // editors make a lot of noise with swap files and the like
+                                }
+                                if event.mask.contains(EventMask::DELETE_SELF) && let Some(node) = node {
+                                    live.changed(Some(node));
+                                    live.changed(project.get_parent(node));
+                                    project.delete(node);
+                                }
+                                if event.mask.contains(EventMask::CLOSE_WRITE) { // MODIFY fires mid-write (right after truncation, even), so we wait for the
+                                                                                 // writer to close the file before looking at it
+                                    if let Some(name) = event.name { // directories also hear about writes to their children. usually the child's own watch
+                                                                     // covers that too, but a file created and written quickly can finish before track_file
+                                                                     // gets its watch added, and a file that failed to load isn't tracked at all
+                                        let name = name.to_str().unwrap();
+                                        if let Some(child) = project.tracked(node, name) {
+                                            modified.insert(child);
+                                        }
+                                        else {
+                                            if let Err(e) = project.track_file(node, name, &mut resolver) {
+                                                println!("{}", project.report(&e));
+                                            }
+                                            live.changed(node);
+                                        }
+                                    }
+                                    else if let Some(node) = node {
+                                        modified.insert(node);
                                     }
                                 }
                                 if event.mask.contains(EventMask::CREATE) {
                                     if let Err(e) = project.track_file(node, event.name.unwrap().to_str().unwrap(), &mut resolver) {
-                                        println!("{}", e);
+                                        println!("{}", project.report(&e));
                                     }
+                                    live.changed(node);
                                 }
                                 if event.mask.contains(EventMask::MOVED_TO) {
                                     if let Err(e) = project.track_file(node, event.name.unwrap().to_str().unwrap(), &mut resolver) {
-                                        println!("{}", e);
+                                        println!("{}", project.report(&e));
                                     }
+                                    live.changed(node);
                                 }
                                 if event.mask.contains(EventMask::MOVED_FROM) {
                                     if let Some(file_id) = project.child_get(node, event.name.unwrap().to_str().unwrap()) {
+                                        live.changed(Some(file_id));
                                         project.delete(file_id);
                                     }
+                                    live.changed(node);
+                                }
+                            }
+                            let mut project = project_clone.lock().unwrap();
+                            for node in modified {
+                                if let Err(e) = project.reload(node, &mut resolver) {
+                                    println!("{}", project.report(&e));
+                                }
+                                live.changed(Some(node));
+                                if project.is_config(node) { // every page can see `site`, so every page has to reload
+                                    match project.reload_config() {
+                                        Ok(()) => { ffi.replace("site", project.config.to_data()); },
+                                        Err(e) => { println!("{}", project.report(&e)); }
+                                    }
+                                    live.changed(None);
                                 }
                             }
+                            discover_emitted_pages(&project, &ffi, cli.max_call_depth);
                         }
                     }
                 });
 
-                println!("Starting development webserver at http://localhost:8080/");
-                rouille::start_server("0.0.0.0:8080", move |request| {
-                    handler(request, &project)
+                println!("Starting development webserver at http://{}/", address.replace("0.0.0.0", "localhost"));
+                rouille::start_server(address, move |request| {
+                    handler(request, &project, &ffi, &live, cli.max_call_depth)
                 });
             }
             else if metadata.file_type().is_file() {
@@ -0,0 +1,35 @@
+// markdown -> html, for content files and the markdown() builtin. rendering is pulldown-cmark's commonmark, plus tables,
+// strikethrough, and task lists.
+// raw html in the markdown is passed through as-is, not sanitized: markdown is for content you wrote, not for content your visitors did.
+
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use pulldown_cmark::{ Parser, Options, html };
+use crate::interpret::{ Data, IndexableData };
+use crate::error::*;
+use crate::frontmatter;
+
+
+pub fn is_markdown(path : &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+
+pub fn document(text : &str) -> SitixPartialResult<BTreeMap<IndexableData, Data>> { // a whole content file: the front matter's keys, plus the rendered
+                                                                                  // html under `body`
+    let (mut table, body) = match frontmatter::split(text) {
+        Some((front_matter, toml, body)) => (frontmatter::parse(front_matter, toml)?, body),
+        None => (BTreeMap::new(), text)
+    };
+    table.insert(IndexableData::String("body".to_string()), Data::String(render(body)));
+    Ok(table)
+}
+
+
+pub fn render(text : &str) -> String {
+    let parser = Parser::new_ext(text, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS);
+    let mut out = String::new();
+    html::push_html(&mut out, parser);
+    out
+}
@@ -0,0 +1,108 @@
+// extension -> mime type mapping, for anything that serves sitix output over http.
+// text types get a utf-8 charset, since that's the only encoding sitix ever writes.
+
+
+use std::path::Path;
+
+
+const MIME_TYPES : &[(&str, &str)] = &[
+    // documents
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("stx", "text/html"), // sitix templates almost always render to html
+    ("xhtml", "application/xhtml+xml"),
+    ("css", "text/css"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("tsv", "text/tab-separated-values"),
+    ("ics", "text/calendar"),
+    ("vtt", "text/vtt"),
+    ("xml", "application/xml"),
+    ("rss", "application/rss+xml"),
+    ("atom", "application/atom+xml"),
+    ("pdf", "application/pdf"),
+    ("rtf", "application/rtf"),
+    ("epub", "application/epub+zip"),
+    // scripts and data
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("json", "application/json"),
+    ("jsonld", "application/ld+json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("wasm", "application/wasm"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("toml", "application/toml"),
+    // images
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("bmp", "image/bmp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("apng", "image/apng"),
+    // fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("eot", "application/vnd.ms-fontobject"),
+    // audio and video
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("oga", "audio/ogg"),
+    ("opus", "audio/opus"),
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"),
+    ("aac", "audio/aac"),
+    ("m4a", "audio/mp4"),
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("webm", "video/webm"),
+    ("ogv", "video/ogg"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    // archives and other downloads
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("7z", "application/x-7z-compressed"),
+    ("bz2", "application/x-bzip2"),
+    ("xz", "application/x-xz")
+];
+
+
+pub fn mime_type(path : &Path, rendered : bool) -> String { // the full content type for a file, charset and all. rendered is whether the file
+                                                            // is sitix output; an unknown extension on one of those is still a page
+    let base = match path.extension() {
+        Some(ext) => {
+            let ext = ext.to_string_lossy().to_lowercase();
+            let fallback = if rendered { "text/html" } else { "application/octet-stream" };
+            MIME_TYPES.iter().find(|(e, _)| *e == ext).map_or(fallback, |(_, mime)| *mime)
+        },
+        None => "text/html" // pretty urls; a page called `about` is still a page
+    };
+    if is_text(base) {
+        format!("{}; charset=utf-8", base)
+    }
+    else {
+        base.to_string()
+    }
+}
+
+
+pub fn is_html(mime : &str) -> bool {
+    mime.starts_with("text/html")
+}
+
+
+fn is_text(mime : &str) -> bool {
+    mime.starts_with("text/") || mime.ends_with("+xml") || mime.ends_with("+json") || matches!(mime, "application/json" | "application/xml" | "application/yaml" | "application/toml")
+}
@@ -4,7 +4,7 @@ use crate::utility::*;
 use crate::ast::*;
 use crate::inflate::TreeChild;
 use crate::inflate::BlockMode;
-use crate::error::{ Error, SitixResult };
+use crate::error::{ Error, SitixResult, Diagnostics };
 
 
 #[derive(Debug, Clone)]
@@ -112,6 +112,7 @@ impl crate::inflate::SitixTree {
                     let first = self.content.peek()?.span;
                     loop {
                         if let TokenType::RightBracket = self.content.peek()?.tp {
+                            self.content.next()?; // empty table, or a trailing comma
                             break;
                         }
                         table.push(self.parse_table_entry()?);
@@ -141,14 +142,7 @@ impl crate::inflate::SitixTree {
                         }
                     } else {
                         if let Some((BlockMode::Else, children)) = self.children.get_mut(1) {
-                            let ret : SitixResult<Vec<SitixExpression>> = children.iter_mut().map(|thing| {
-                                match thing {
-                                    TreeChild::Text(text, span) => Ok(SitixExpression::Text(text.clone(), span.clone())),
-                                    TreeChild::Tree(tree) => tree.parse(None) // AHAHAHAA RECURSION HAHAAHA BWAHAALKHASDLFHASDLFH
-                                                                          // [a bit later] sometimes I read comments I wrote and then I feel sad
-                                }
-                            }).collect();
-                            Some(Box::new(Expression::SitixExpression(ret?)))
+                            Some(Box::new(Expression::SitixExpression(Self::try_parse_ext(children, &mut self.errors))))
                         }
                         else {
                             None
@@ -205,7 +199,7 @@ impl crate::inflate::SitixTree {
         }
         else { // this *would* be an eof, but there's a chance for recovery!
             if let Some((BlockMode::Main, children)) = self.children.get_mut(0) {
-                Ok(Expression::SitixExpression(Self::try_parse_ext(children)?))
+                Ok(Expression::SitixExpression(Self::try_parse_ext(children, &mut self.errors)))
             }
             else {
                 Err(self.content.unexpected_eof())
@@ -213,15 +207,56 @@ impl crate::inflate::SitixTree {
         }
     }
 
-    fn try_parse_ext(children : &mut Vec<TreeChild>) -> SitixResult<Vec<SitixExpression>> {
+    fn try_parse_ext(children : &mut [TreeChild], errors : &mut Vec<Error>) -> Vec<SitixExpression> {
         children.iter_mut().map(|thing| {
             match thing {
-                TreeChild::Text(text, span) => Ok(SitixExpression::Text(text.clone(), span.clone())),
-                TreeChild::Tree(tree) => tree.parse(None) // AHAHAHAA RECURSION HAHAAHA BWAHAALKHASDLFHASDLFH
+                TreeChild::Text(text, span) => SitixExpression::Text(text.clone(), span.clone()),
+                TreeChild::Tree(tree) => { // AHAHAHAA RECURSION HAHAAHA BWAHAALKHASDLFHASDLFH
+                                           // [a bit later] sometimes I read comments I wrote and then I feel sad
+                    let ret = tree.parse(None);
+                    errors.append(&mut tree.errors);
+                    ret.unwrap_or_else(|e| { // a broken block doesn't stop us from parsing its siblings
+                        errors.push(e);
+                        SitixExpression::Text(String::new(), Span::identity())
+                    })
+                }
             }
         }).collect()
     }
 
+    fn recover(&mut self, e : Error) -> bool { // record an error and skip to the start of the next statement.
+                                               // returns false if there isn't a next statement in this block.
+        self.errors.push(e);
+        let mut depth = 0usize;
+        while let Ok(tok) = self.content.peek() {
+            match tok.tp {
+                TokenType::Semicolon if depth == 0 => {
+                    self.content.next().unwrap();
+                    return true;
+                },
+                TokenType::RightBrace if depth == 0 => { // the end of the braced block we're in
+                    return false;
+                },
+                TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => {
+                    depth += 1;
+                },
+                TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                    depth = depth.saturating_sub(1);
+                },
+                _ => {}
+            }
+            self.content.next().unwrap();
+        }
+        false
+    }
+
+    fn at_block_end(&self) -> bool {
+        match self.content.peek() {
+            Err(_) => true,
+            Ok(tok) => matches!(tok.tp, TokenType::RightBrace)
+        }
+    }
+
     fn parse_csl(&mut self, end : TokenType) -> SitixResult<Vec<Expression>> { // Comma Separated List
         let mut out = vec![];
         loop {
@@ -269,6 +304,21 @@ impl crate::inflate::SitixTree {
                 }
                 out = Expression::Call(Box::new(out), args);
             }
+            else if let TokenType::LeftBracket = tok.tp {
+                self.content.next()?;
+                let index = if let TokenType::RightBracket = self.content.peek()?.tp {
+                    None
+                }
+                else {
+                    Some(Box::new(self.parse_expression()?))
+                };
+                self.content.pcheck(TokenType::RightBracket)?;
+                out = Expression::Index(tok.span.merge(self.content.get_last_span()), Box::new(out), index);
+            }
+            else if let TokenType::PlusPlus | TokenType::MinusMinus = tok.tp {
+                self.content.next()?;
+                out = Self::increment(out, tok);
+            }
             else {
                 break;
             }
@@ -289,7 +339,7 @@ impl crate::inflate::SitixTree {
                     if self.children.len() > 1 {
                         for (mode, contents) in self.children[1..].iter_mut() {
                             if let BlockMode::List = mode {
-                                args.push(Expression::SitixExpression(Self::try_parse_ext(contents)?));
+                                args.push(Expression::SitixExpression(Self::try_parse_ext(contents, &mut self.errors)));
                             }
                             else {
                                 panic!("expected list contents (todo: make this a real error)");
@@ -303,6 +353,11 @@ impl crate::inflate::SitixTree {
         Ok(lhs)
     }
 
+    fn increment(target : Expression, op : Token) -> Expression { // both x ++ and ++ x are sugar for x += 1
+        let op_kind = if let TokenType::PlusPlus = op.tp { CompoundOp::Add } else { CompoundOp::Sub };
+        Expression::CompoundAssignment(Box::new(target), op_kind, Box::new(Expression::Literal(op.span, Literal::Number(1.0))))
+    }
+
     fn parse_unary(&mut self) -> SitixResult<Expression> {
         if let Ok(tok) = self.content.peek() {
             Ok(match tok.tp {
@@ -314,6 +369,10 @@ impl crate::inflate::SitixTree {
                     self.content.next()?;
                     Expression::Unary(Unary::Negative(tok.span, Box::new(self.parse_unary()?)))
                 },
+                TokenType::PlusPlus | TokenType::MinusMinus => {
+                    self.content.next()?;
+                    Self::increment(self.parse_unary()?, tok)
+                },
                 _ => {
                     self.parse_dotaccess()?
                 }
@@ -439,11 +498,21 @@ impl crate::inflate::SitixTree {
     fn parse_assignment(&mut self) -> SitixResult<Expression> {
         let expr = self.parse_equality()?;
         if let Ok(tok) = self.content.peek() {
-            if let TokenType::Eq = tok.tp {
-                self.content.next()?;
-                let value = self.parse_assignment()?;
-                return Ok(Expression::Assignment(Box::new(expr), Box::new(value)));
-            }
+            let compound = match tok.tp {
+                TokenType::Eq => {
+                    self.content.next()?;
+                    let value = self.parse_assignment()?;
+                    return Ok(Expression::Assignment(Box::new(expr), Box::new(value)));
+                },
+                TokenType::PlusEq => CompoundOp::Add,
+                TokenType::MinusEq => CompoundOp::Sub,
+                TokenType::StarEq => CompoundOp::Mul,
+                TokenType::SlashEq => CompoundOp::Div,
+                _ => { return Ok(expr); }
+            };
+            self.content.next()?;
+            let value = self.parse_assignment()?;
+            return Ok(Expression::CompoundAssignment(Box::new(expr), compound, Box::new(value)));
         }
         Ok(expr)
     }
@@ -459,11 +528,29 @@ impl crate::inflate::SitixTree {
                     self.content.next()?;
                     return Ok(Statement::Debugger(outer_tok.span));
                 },
-                TokenType::Let | TokenType::Global => {
+                TokenType::Param => { // param name = default. the default is optional
+                    self.content.next()?;
+                    let tok = self.content.next()?;
+                    if let TokenType::Literal(Literal::Ident(ident)) = tok.tp {
+                        let default = if let Ok(Token { tp : TokenType::Eq, .. }) = self.content.peek() {
+                            self.content.next()?;
+                            self.parse_expression()?
+                        }
+                        else {
+                            Expression::Nil(tok.span.clone())
+                        };
+                        return Ok(Statement::UnboundParamAssign(tok.span, ident, Box::new(default)));
+                    }
+                    else {
+                        return Err(Error::expected_abstract("identifier", tok.span));
+                    }
+                },
+                TokenType::Let | TokenType::Global | TokenType::Export => {
                     self.content.next()?;
                     let pattern = match outer_tok.tp {
                         TokenType::Let => Statement::UnboundLetAssign,
                         TokenType::Global => Statement::UnboundGlobalAssign,
+                        TokenType::Export => Statement::UnboundExportAssign,
                         _ => panic!("unreachable")
                     };
                     let tok = self.content.next()?;
@@ -490,7 +577,16 @@ impl crate::inflate::SitixTree {
         let mut tail;
         self.content.start_span_tracker();
         loop {
-            tail = Some(self.parse_statement()?);
+            tail = match self.parse_statement() {
+                Ok(statement) => Some(statement),
+                Err(e) => { // skip the broken statement and carry on, so we can report more than one error per run
+                    tail = None;
+                    if !self.recover(e) || self.at_block_end() {
+                        break;
+                    }
+                    continue;
+                }
+            };
             match self.content.peek() { // check this before doing semicolon checks; if the output is ended without
                                         // a semicolon, the preceding statement is a tail
                 Err(_) => {
@@ -502,7 +598,11 @@ impl crate::inflate::SitixTree {
                     }
                 }
             }
-            self.content.pcheck(TokenType::Semicolon)?; // if we *didn't* find an eob, the next token *must* be a semicolon!
+            if let Err(e) = self.content.pcheck(TokenType::Semicolon) { // if we *didn't* find an eob, the next token *must* be a semicolon!
+                if !self.recover(e) {
+                    break;
+                }
+            }
             inner.push(tail.unwrap());
             tail = None;
             match self.content.peek() {
@@ -523,6 +623,18 @@ impl crate::inflate::SitixTree {
         })
     }
 
+    pub fn parse_root(&mut self, filename : String) -> Result<SitixExpression, Diagnostics> { // parse a whole file, reporting every error we found along the way
+        let ret = self.parse(Some(filename));
+        let mut errors = std::mem::take(&mut self.errors);
+        match ret {
+            Ok(expr) if errors.is_empty() => { return Ok(expr); },
+            Ok(_) => {},
+            Err(e) => errors.push(e)
+        }
+        errors.sort_by_key(|e| e.span.start_char);
+        Err(Diagnostics(errors))
+    }
+
     pub fn parse(&mut self, file_data : Option<String>) -> SitixResult<SitixExpression> { // why use SitixExpression here?
         // I flipflopped on this a bit, but in the
         // end it's simpler to get a sitix expression from this function
@@ -530,22 +642,26 @@ impl crate::inflate::SitixTree {
         // guaranteed to *always* return SitixExpression::Block(_).
         // [later] I'm pretty glad I chose this pattern! It makes the following trickery a lot simpler.
         if let Some(filename) = file_data {
-            let Block { inner : old_inner, tail, span } = self.parse_block()?;
-            let mut inner = vec![
-                Statement::UnboundGlobalAssign(
-                    Span::identity(),
-                    "__filename__".to_string(),
-                    Box::new(Expression::Literal(Span::identity(),
-                        Literal::String(filename)
-                    ))
-                )
-            ];
-            inner.extend(old_inner);
-            Ok(SitixExpression::Block(Block {
-                inner,
-                tail,
-                span
-            }))
+            let mut block = self.parse_block()?;
+            // the root of a file is always a block with no statements and a sitix expression for a tail (see parse_primary's recovery).
+            // __filename__ is exported from a block tucked in at the very start of that sitix expression, so it lands in the file's property
+            // map alongside everything else the file exports.
+            if let Some(Statement::Expression(tail)) = &mut block.tail && let Expression::SitixExpression(children) = tail.as_mut() {
+                children.insert(0, SitixExpression::Block(Block {
+                    inner : vec![
+                        Statement::UnboundExportAssign(
+                            Span::identity(),
+                            "__filename__".to_string(),
+                            Box::new(Expression::Literal(Span::identity(),
+                                Literal::String(filename)
+                            ))
+                        )
+                    ],
+                    tail : None,
+                    span : Span::identity()
+                }));
+            }
+            Ok(SitixExpression::Block(block))
         }
         else {
             Ok(SitixExpression::Block(self.parse_block()?))
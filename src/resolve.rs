@@ -3,12 +3,16 @@ use crate::ast::*;
 use std::collections::HashMap;
 use std::sync::Arc;
 use crate::ffi::*;
+use crate::error::{ Error, Diagnostics };
+use crate::utility::edit_distance;
 
 
 pub struct ResolverState {
     scopes : Vec<HashMap<String, usize>>,
+    sitix_scopes : Vec<usize>, // indices into `scopes` of every scope opened by a sitix expression. exports are bound in the innermost one.
     top_var : usize,
-    ffi : Arc<ForeignFunctionInterface>
+    ffi : Arc<ForeignFunctionInterface>,
+    errors : Vec<Error> // unresolved identifiers. we keep going after one so every typo in a file is reported at once
 }
 
 
@@ -16,8 +20,10 @@ impl ResolverState {
     pub fn new(ffi : Arc<ForeignFunctionInterface>) -> ResolverState {
         ResolverState {
             scopes : vec![HashMap::new()],
+            sitix_scopes : vec![],
             top_var : ffi.top_index + 1,
-            ffi
+            ffi,
+            errors : vec![]
         }
     }
 
@@ -41,6 +47,23 @@ impl ResolverState {
         self.top_var
     }
 
+    fn create_export(&mut self, name : String) -> usize { // exports are visible to every block in the sitix expression that contains them
+        self.top_var += 1;
+        let scope = *self.sitix_scopes.last().unwrap_or(&(self.scopes.len() - 1));
+        self.scopes[scope].insert(name, self.top_var);
+        self.top_var
+    }
+
+    fn open_sitix_scope(&mut self) {
+        self.open_scope();
+        self.sitix_scopes.push(self.scopes.len() - 1);
+    }
+
+    fn close_sitix_scope(&mut self) {
+        self.sitix_scopes.pop();
+        self.close_scope();
+    }
+
     fn find(&mut self, name : &String) -> Option<usize> {
         for scope in self.scopes.iter().rev() {
             if let Some(ret) = scope.get(name) {
@@ -50,14 +73,35 @@ impl ResolverState {
         self.ffi.find(name)
     }
 
+    fn suggest(&self, name : &str) -> Option<String> { // find the visible name closest to a misspelled one
+        let threshold = (name.chars().count() / 3).max(1);
+        self.scopes.iter().flat_map(|scope| scope.keys()).chain(self.ffi.names())
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= threshold)
+            .min()
+            .map(|(_, candidate)| candidate.clone())
+    }
+
     pub fn seal(&mut self) { // discard the symbol table so we can begin resolving another file in the same project
         self.scopes.clear();
+        self.sitix_scopes.clear();
     }
 }
 
 
 impl SitixExpression {
-    pub fn resolve(self, r : &mut ResolverState) -> Self {
+    pub fn resolve(self, r : &mut ResolverState) -> Result<Self, Diagnostics> {
+        let ret = self.resolve_inner(r);
+        let errors = std::mem::take(&mut r.errors);
+        if errors.is_empty() {
+            Ok(ret)
+        }
+        else {
+            Err(Diagnostics(errors))
+        }
+    }
+
+    fn resolve_inner(self, r : &mut ResolverState) -> Self {
         match self {
             Self::Block(b) => {
                 r.open_scope();
@@ -88,11 +132,19 @@ impl Statement {
             Self::Expression(expr) => Self::Expression(Box::new(expr.resolve(r))),
             Self::UnboundLetAssign(tok, ident, expr) => {
                 let id = r.create(ident);
-                Self::Assign(tok, id, Box::new(expr.resolve(r)), None)
+                Self::Assign(tok, id, Box::new(expr.resolve(r)), Binding::Local)
             },
             Self::UnboundGlobalAssign(tok, ident, expr) => {
-                let id = r.create_global(ident.clone());
-                Self::Assign(tok, id, Box::new(expr.resolve(r)), Some(ident))
+                let id = r.create_global(ident);
+                Self::Assign(tok, id, Box::new(expr.resolve(r)), Binding::Global)
+            },
+            Self::UnboundExportAssign(tok, ident, expr) => {
+                let id = r.create_export(ident.clone());
+                Self::Assign(tok, id, Box::new(expr.resolve(r)), Binding::Export(ident))
+            },
+            Self::UnboundParamAssign(tok, ident, expr) => {
+                let id = r.create_export(ident.clone()); // parameters are visible to the whole file, just like exports
+                Self::Assign(tok, id, Box::new(expr.resolve(r)), Binding::Param(ident))
             },
             _ => self
         }
@@ -108,17 +160,31 @@ impl Expression {
             Self::Grouping(l) => Self::Grouping(Box::new(l.resolve(r))),
             Self::Braced(l) => Self::Braced(Box::new(l.resolve(r))),
             Self::SitixExpression(l) => {
-                Self::SitixExpression(l.into_iter().map(|expr| expr.resolve(r)).collect())
+                r.open_sitix_scope();
+                let ret = Self::SitixExpression(l.into_iter().map(|expr| expr.resolve_inner(r)).collect());
+                r.close_sitix_scope();
+                ret
             },
             Self::True(s) => Self::True(s),
             Self::False(s) => Self::False(s),
             Self::Nil(s) => Self::Nil(s),
             Self::UnboundVariableAccess(span, name) => {
-                Self::VariableAccess(span, r.find(&name).expect(&name)) // TODO: don't unwrap here
+                if let Some(id) = r.find(&name) {
+                    Self::VariableAccess(span, id)
+                }
+                else { // leave it unbound; the errors stop this tree from ever reaching the interpreter
+                    let suggestion = r.suggest(&name);
+                    r.errors.push(Error::undefined_variable(&name, suggestion, span.clone()));
+                    Self::UnboundVariableAccess(span, name)
+                }
             },
             Self::Assignment(variable, value) => Self::Assignment(Box::new(variable.resolve(r)), Box::new(value.resolve(r))),
+            Self::CompoundAssignment(variable, op, value) => Self::CompoundAssignment(Box::new(variable.resolve(r)), op, Box::new(value.resolve(r))),
             Self::IfBranch(span, condition, truthy, falsey) => Self::IfBranch(span, Box::new(condition.resolve(r)), Box::new(truthy.resolve(r)), match falsey { Some(falsey) => Some(Box::new(falsey.resolve(r))), None => None }),
-            Self::Table(span, table) => Self::Table(span, table.into_iter().map(|t| TableEntry { content : Box::new(t.content.resolve(r)), label : t.label }).collect()),
+            Self::Table(span, table) => Self::Table(span, table.into_iter().map(|t| TableEntry {
+                label : t.label.map(|label| Box::new(label.resolve(r))), // labels are computed keys, so they're expressions like any other
+                content : Box::new(t.content.resolve(r))
+            }).collect()),
             Self::While(span, cond, body) => Self::While(span, Box::new(cond.resolve(r)), Box::new(body.resolve(r))),
             Self::Call(fun, args) => {
                 let fun = fun.resolve(r);
@@ -151,7 +217,8 @@ impl Expression {
                 }
             },
             Self::Each(_, _, _, _, _) => panic!("unreachable"),
-            Self::DotAccess(expr, s) => Self::DotAccess(Box::new(expr.resolve(r)), s)
+            Self::DotAccess(expr, s) => Self::DotAccess(Box::new(expr.resolve(r)), s),
+            Self::Index(span, expr, index) => Self::Index(span, Box::new(expr.resolve(r)), index.map(|index| Box::new(index.resolve(r))))
         }
     }
 }
@@ -194,3 +261,31 @@ impl Binary {
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::ffi::ForeignFunctionInterface;
+    use crate::resolve::ResolverState;
+
+    fn suggest(visible : &[&str], name : &str) -> Option<String> {
+        let mut r = ResolverState::new(Arc::new(ForeignFunctionInterface::new()));
+        for visible in visible {
+            r.create(visible.to_string());
+        }
+        r.suggest(name)
+    }
+
+    #[test]
+    fn suggests_transposed_names() {
+        assert_eq!(suggest(&["title", "date"], "titel").as_deref(), Some("title"));
+        assert_eq!(suggest(&["date"], "dtae").as_deref(), Some("date"));
+    }
+
+    #[test]
+    fn suggests_typos_and_skips_unrelated_names() {
+        assert_eq!(suggest(&["title", "author"], "autor").as_deref(), Some("author"));
+        assert_eq!(suggest(&["title", "author"], "body"), None);
+    }
+}
@@ -20,7 +20,7 @@ pub enum TokenType {
     And, Or, Not,
     While, Each, If, Else,
     True, False, Nil,
-    Let, Global, Fun,
+    Let, Global, Export, Param, Fun,
     Debugger
 }
 
@@ -42,26 +42,6 @@ impl Span {
         Self::new(0, 0, "unknown_file".to_string())
     }
 
-    pub fn get_line_col(&self) -> (usize, usize) {
-        let mut line = 1;
-        let mut col = 0;
-        let file = match std::fs::read_to_string(&self.filename) { Ok(file) => file, Err(_) => {return (0, 0);}};
-        let iter = file.chars();
-        let mut cnt = self.start_char;
-        for ch in iter {
-            cnt -= 1;
-            if ch == '\n' {
-                col = 0;
-                line += 1;
-            }
-            if cnt == 0 {
-                break;
-            }
-            col += 1;
-        }
-        (line, col)
-    }
-
     pub fn merge(self, other : Span) -> Span {
         Span {
             filename : self.filename,
@@ -71,6 +51,67 @@ impl Span {
     }
 }
 
+// the text of a loaded file, kept around so errors can quote it
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    lines : Vec<Vec<char>>,
+    line_starts : Vec<usize> // the char index each line begins at
+}
+
+
+pub type SourceMap = std::collections::HashMap<String, SourceFile>; // keyed by Span::filename
+
+
+impl SourceFile {
+    pub fn new(source : &str) -> Self {
+        let mut lines = vec![];
+        let mut line_starts = vec![];
+        let mut start = 0;
+        for line in source.split('\n') {
+            let line : Vec<char> = line.chars().collect();
+            line_starts.push(start);
+            start += line.len() + 1;
+            lines.push(line);
+        }
+        Self { lines, line_starts }
+    }
+
+    pub fn line_col(&self, index : usize) -> (usize, usize) { // both zero-indexed
+        let line = self.line_starts.partition_point(|start| *start <= index).max(1) - 1;
+        (line, index - self.line_starts[line])
+    }
+
+    pub fn line(&self, line : usize) -> &[char] {
+        &self.lines[line]
+    }
+}
+
+
+// optimal string alignment distance, for "did you mean" suggestions: levenshtein, plus swapping two adjacent characters counts as one edit
+pub fn edit_distance(a : &str, b : &str) -> usize {
+    let a : Vec<char> = a.chars().collect();
+    let b : Vec<char> = b.chars().collect();
+    let mut before : Vec<usize> = Vec::new(); // the row for a[..i - 1], for transpositions
+    let mut previous : Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            row[j] = (previous[j] + 1).min(row[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous[b.len()]
+}
+
+
+pub fn escape_html(text : &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub tp : TokenType,